@@ -13,15 +13,12 @@ pub fn read_balance(e: &Env, addr: Address) -> Map<u32, i128> {
 
 fn write_balance(e: &Env, addr: Address, token_id: u32, amount: i128) {
     let key = NFTDataKey::Balance(addr);
-    let token_balance_map: soroban_sdk::Map<u32, i128> = e
+    let mut token_balance_map: soroban_sdk::Map<u32, i128> = e
         .storage()
         .persistent()
         .get(&key)
         .unwrap_or(map![&e, (1, 0), (2, 0), (3, 0)]);
-    let _token_key = token_balance_map.keys().set(
-        token_id.try_into().unwrap_or(0),
-        amount.try_into().unwrap_or(0),
-    );
+    token_balance_map.set(token_id, amount);
 
     e.storage().persistent().set(&key, &token_balance_map);
     e.storage()
@@ -29,37 +26,49 @@ fn write_balance(e: &Env, addr: Address, token_id: u32, amount: i128) {
         .bump(&key, BALANCE_BUMP_AMOUNT, BALANCE_BUMP_AMOUNT + 100);
 }
 
+pub fn read_supply(e: &Env, token_id: u32) -> i128 {
+    let key = NFTDataKey::Supply(token_id);
+    e.storage().instance().get(&key).unwrap_or(0)
+}
+
+pub fn write_supply(e: &Env, token_id: u32, amount: i128) {
+    let key = NFTDataKey::Supply(token_id);
+    e.storage().instance().set(&key, &amount);
+}
+
 pub fn receive_balance(e: &Env, addr: Address, token_id: u32, amount: i128) {
     let balance = read_balance(e, addr.clone());
-    // if !is_authorized(e, addr.clone()) {
-    //     panic!("can't receive when deauthorized");
-    // }
+    if !is_authorized(e, addr.clone()) {
+        panic!("can't receive when deauthorized");
+    }
     let balance_amount: Option<i128> = balance.get(token_id.try_into().unwrap_or(0));
     let new_balance_amount = balance_amount.unwrap_or(0) + amount;
     write_balance(e, addr, token_id, new_balance_amount);
 }
 
-pub fn spend_balance(e: &Env, addr: Address, token_id: u32) {
-    // let balance = read_balance(e, addr.clone());
-    // if !is_authorized(e, addr.clone()) {
-    //     panic!("can't spend when deauthorized");
-    // }
-    // if balance < amount {
-    //     panic!("insufficient balance");
-    // }
-    write_balance(e, addr, token_id, 0);
+pub fn spend_balance(e: &Env, addr: Address, token_id: u32, amount: i128) {
+    let balance = read_balance(e, addr.clone()).get(token_id).unwrap_or(0);
+    if !is_authorized(e, addr.clone()) {
+        panic!("can't spend when deauthorized");
+    }
+    if balance == 0 || balance < amount {
+        panic!("insufficient balance");
+    }
+    write_balance(e, addr, token_id, balance - amount);
 }
 
-// pub fn is_authorized(e: &Env, addr: Address) -> bool {
-//     let key = NFTDataKey::State(addr);
-//     if let Some(state) = e.storage().persistent().get::<NFTDataKey, bool>(&key) {
-//         state
-//     } else {
-//         true
-//     }
-// }
+pub fn is_authorized(e: &Env, addr: Address) -> bool {
+    let key = NFTDataKey::State(addr);
+    e.storage()
+        .persistent()
+        .get::<NFTDataKey, bool>(&key)
+        .unwrap_or(true)
+}
 
-// pub fn write_authorization(e: &Env, addr: Address, is_authorized: bool) {
-//     let key = NFTDataKey::State(addr);
-//     e.storage().persistent().set(&key, &is_authorized);
-// }
+pub fn write_authorization(e: &Env, addr: Address, is_authorized: bool) {
+    let key = NFTDataKey::State(addr);
+    e.storage().persistent().set(&key, &is_authorized);
+    e.storage()
+        .persistent()
+        .bump(&key, BALANCE_BUMP_AMOUNT, BALANCE_BUMP_AMOUNT + 100);
+}
@@ -0,0 +1,63 @@
+use soroban_sdk::{symbol_short, Address, Env, Symbol};
+
+pub(crate) fn battle_created(e: &Env, name: Symbol, creator: Address) {
+    let topics = (symbol_short!("battle"), symbol_short!("created"));
+    e.events().publish(topics, (name, creator));
+}
+
+pub(crate) fn player_joined(e: &Env, name: Symbol, player: Address) {
+    let topics = (symbol_short!("battle"), symbol_short!("joined"));
+    e.events().publish(topics, (name, player));
+}
+
+// The choice itself is left out so the opponent can't read it before moving.
+pub(crate) fn move_registered(e: &Env, name: Symbol, player: Address) {
+    let topics = (symbol_short!("battle"), symbol_short!("moved"));
+    e.events().publish(topics, (name, player));
+}
+
+pub(crate) fn battle_ended(e: &Env, name: Symbol, winner: Address) {
+    let topics = (symbol_short!("battle"), symbol_short!("ended"));
+    e.events().publish(topics, (name, winner));
+}
+
+pub(crate) fn battle_cancelled(e: &Env, name: Symbol, creator: Address) {
+    let topics = (symbol_short!("battle"), symbol_short!("cancelled"));
+    e.events().publish(topics, (name, creator));
+}
+
+pub(crate) fn invite_declined(e: &Env, name: Symbol, player: Address) {
+    let topics = (symbol_short!("battle"), symbol_short!("declined"));
+    e.events().publish(topics, (name, player));
+}
+
+pub(crate) fn battle_rematched(e: &Env, name: Symbol, player: Address) {
+    let topics = (symbol_short!("battle"), symbol_short!("rematch"));
+    e.events().publish(topics, (name, player));
+}
+
+pub(crate) fn battle_drawn(e: &Env, name: Symbol) {
+    let topics = (symbol_short!("battle"), symbol_short!("drawn"));
+    e.events().publish(topics, name);
+}
+
+pub(crate) fn sword_forged(e: &Env, player: Address, class: u32) {
+    let topics = (symbol_short!("sword"), symbol_short!("forged"));
+    e.events().publish(topics, (player, class));
+}
+
+pub(crate) fn sword_melted(e: &Env, player: Address, class: u32) {
+    let topics = (symbol_short!("sword"), symbol_short!("melted"));
+    e.events().publish(topics, (player, class));
+}
+
+pub(crate) fn sword_fused(e: &Env, player: Address, class: u32) {
+    let topics = (symbol_short!("sword"), symbol_short!("fused"));
+    e.events().publish(topics, (player, class));
+}
+
+// A level-up couldn't raise the stat any further.
+pub(crate) fn stat_capped(e: &Env, player: Address, stat: Symbol) {
+    let topics = (symbol_short!("player"), symbol_short!("capped"));
+    e.events().publish(topics, (player, stat));
+}
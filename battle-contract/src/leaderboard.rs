@@ -0,0 +1,30 @@
+use crate::PlayerStat;
+use soroban_sdk::{Env, Vec};
+
+// Selects up to `limit` players with the highest `rank`, best first. Soroban's
+// Vec has no sort, so this is a bounded selection: each pass picks the best of
+// the remaining players, which costs O(n * limit) rather than sorting the whole
+// list. Ties keep registration order.
+pub fn top_players<K: Ord>(
+    e: &Env,
+    stats: Vec<PlayerStat>,
+    limit: u32,
+    rank: impl Fn(&PlayerStat) -> K,
+) -> Vec<PlayerStat> {
+    let mut remaining = stats;
+    let mut top = Vec::new(e);
+    while top.len() < limit && !remaining.is_empty() {
+        let mut best_index = 0;
+        let mut best_rank = rank(&remaining.get_unchecked(0));
+        for i in 1..remaining.len() {
+            let candidate = rank(&remaining.get_unchecked(i));
+            if candidate > best_rank {
+                best_index = i;
+                best_rank = candidate;
+            }
+        }
+        top.push_back(remaining.get_unchecked(best_index));
+        remaining.remove(best_index);
+    }
+    top
+}
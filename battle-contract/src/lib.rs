@@ -1,29 +1,243 @@
 #![no_std]
 
+mod admin;
 mod balance;
+mod events;
+mod leaderboard;
+mod owner;
+mod rating;
+mod rng;
 mod storage_types;
 mod sword_contract;
-pub use crate::sword_contract::{NFTCollectionFactory, SwordContract};
+pub use crate::sword_contract::{NFTCollectionFactory, SwordContract, TokenMetadata};
 
+use crate::admin::{has_administrator, read_administrator, write_administrator};
+use crate::leaderboard::top_players;
+use crate::owner::read_owned_tokens;
+use crate::rating::{update_ratings, STARTING_RATING};
+use crate::storage_types::{DAY_IN_LEDGERS, INSTANCE_BUMP_AMOUNT, INSTANCE_LIFETIME_THRESHOLD};
+use core::cmp::{Ordering, Reverse};
+use soroban_sdk::xdr::ToXdr;
 use soroban_sdk::{
-    contract, contractimpl, contracttype, map, Address, Env, Error, Map, Symbol, Vec,
+    contract, contracterror, contractimpl, contracttype, map, panic_with_error, symbol_short,
+    token, vec, Address, Bytes, BytesN, Env, Error, Map, String, Symbol, TryFromVal, Val, Vec,
 };
 
+/// The version of the contract's storage layout. Bump it whenever a stored
+/// type such as `PlayerStat` or `Battle` changes shape. `Battle` gained
+/// `round_count` in version 2, `first_mover` in 3, `specials` in 4 and
+/// `invited_at` in 5. In 6 its `winner`, `first_mover` and `invited_at`,
+/// and the `PlayerStat` `first_win_at`, became optional.
+pub const CONTRACT_VERSION: u32 = 6;
+
+/// The most players a single battle can hold.
+pub const MAX_PLAYERS: u32 = 4;
+
+/// The maximum number of bot battles a single `tick` will advance.
+pub const MAX_TICK_BATTLES: u32 = 10;
+
+/// Seconds a player may wait on their opponent's move before claiming the win,
+/// until the admin configures a timeout.
+pub const MOVE_TIMEOUT: u64 = 86400;
+
+/// The health a player without a sword can heal up to.
+pub const MAX_HEALTH: u32 = 100;
+
+/// The percentage of a player's maximum health that healing can restore them to.
+pub const HEAL_CAP_PERCENT: u32 = 100;
+
+/// The experience awarded for a PvP win until the admin configures rewards.
+pub const DEFAULT_PVP_WIN_REWARD: u32 = 10;
+
+/// The experience awarded for a win against the bot until the admin configures rewards.
+pub const DEFAULT_BOT_WIN_REWARD: u32 = 2;
+
+/// The most player statistics a single `get_player_stats_page` call returns.
+pub const MAX_PAGE_SIZE: u32 = 50;
+
+/// The experience awarded to the loser of a battle, so every fight counts
+/// toward a level.
+pub const LOSS_EXPERIENCE: u32 = 2;
+
+/// The experience needed for each level. A player's level is
+/// `1 + experience / EXPERIENCE_PER_LEVEL`.
+pub const EXPERIENCE_PER_LEVEL: u32 = 50;
+
+/// The attack a player permanently gains for every level above the first.
+pub const LEVEL_ATTACK_BONUS: u32 = 2;
+
+/// The defense a player permanently gains for every level above the first.
+pub const LEVEL_DEFENSE_BONUS: u32 = 1;
+
+/// The highest base health the admin can give new players.
+pub const MAX_BASE_HEALTH: u32 = 1_000;
+
+/// The highest base attack a player can be raised to.
+pub const MAX_ATTACK: u32 = 1_000;
+
+/// The highest base defense a player can be raised to.
+pub const MAX_DEFENSE: u32 = 1_000;
+
+/// The ledgers an invited player has to accept or decline before the
+/// challenger can reclaim the invite.
+pub const INVITE_EXPIRY_LEDGERS: u32 = DAY_IN_LEDGERS;
+
+/// The most recent moves kept in each player's move history.
+pub const MAX_MOVE_HISTORY: u32 = 20;
+
+/// The rounds a battle lasts before the player with the most health left is
+/// declared the winner. Players tied for the most health draw.
+pub const MAX_ROUNDS: u64 = 50;
+
+/// The damage of a heavy attack, as a percentage of a light attack's.
+pub const HEAVY_ATTACK_PERCENT: u32 = 150;
+
+/// The health a potion restores.
+pub const POTION_HEAL: u32 = 25;
+
+/// Added to a sword class to get the class of the enhanced sword two of them
+/// fuse into, so Longswords, Sabres and Claymores fuse into classes 4, 5 and 6.
+pub const ENHANCED_CLASS_OFFSET: u32 = 3;
+
+/// The number of spectators a battle allows until the admin configures a cap.
+pub const DEFAULT_MAX_SPECTATORS: u32 = 100;
+
+/// A new player's attack until the admin configures base stats.
+pub const DEFAULT_BASE_ATTACK: u32 = 10;
+
+/// A new player's defense until the admin configures base stats.
+pub const DEFAULT_BASE_DEFENSE: u32 = 10;
+
 /// Enum representing keys for data storage.
-///
-/// # Variants
-///
-/// * `Player` - The key for a player.
-/// * `Battle` - The key for a battle.
-/// * `Players` - The key for the list of players.
-/// * `Battles` - The key for the list of battles.
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum DataKey {
+    /// The key for a player.
     Player(Address),
+    /// The key for a battle.
     Battle(Symbol),
+    /// The key for the list of players.
     Players,
+    /// The key for the list of battles.
     Battles,
+    /// The key for the number of registered players.
+    PlayerCount,
+    /// The key for the number of battles in the battle list.
+    BattleCount,
+    /// The key for the contract administrator.
+    Admin,
+    /// The key for the address of the sword NFT contract.
+    SwordContract,
+    /// The key for the win rewards.
+    Rewards,
+    /// The key for whether bot battles are allowed.
+    BotsEnabled,
+    /// The key for the number of spectators a battle allows.
+    MaxSpectators,
+    /// The key for the seconds a player has to make their move.
+    MoveTimeout,
+    /// The key for a battle's spectators.
+    Spectators(Symbol),
+    /// The key for the names of battles that have ended.
+    BattleArchive,
+    /// The key for the log of ended two-player battles, oldest first.
+    BattleLog,
+    /// The key for the statistics new players start with.
+    BaseStats,
+    /// The key for the percent of attack lost per resolved round.
+    AttackDecay,
+    /// The key for the players waiting to be paired.
+    Matchmaking,
+    /// The key for the number of battles matchmaking has created.
+    MatchCount,
+    /// The key for how many swords of a class a player may ever forge.
+    ForgeLimit(u32),
+    /// The key for how many swords of a class a player has forged.
+    ForgeCount(Address, u32),
+    /// The key for how many swords of a class have been forged in total.
+    ForgeTotal(u32),
+    /// The key for the storage layout version the stored players follow.
+    SchemaVersion,
+    /// The key for the number of potions a player holds.
+    Potions(Address),
+    /// The key for the moves a player has submitted, oldest first.
+    PlayerMoves(Address),
+    /// The key for the address of the token battles are wagered in.
+    WagerToken,
+    /// The key for the amount each player of a battle has wagered.
+    Wager(Symbol),
+    /// The key for the player a battle is waiting on to accept its invite.
+    Invite(Symbol),
+    /// The key for whether new battles and forging are halted.
+    Paused,
+}
+
+/// Errors returned by the battle contract.
+///
+/// # Variants
+///
+/// * `BotsDisabled` - Bot battles are turned off for this deployment.
+/// * `ForgeLimitReached` - The player hit the class's forge limit.
+/// * `CannotBattleSelf` - The player is already a participant of the battle.
+/// * `SeedMismatch` - The revealed seed doesn't match the commitment.
+/// * `BotMoveNotAllowed` - The bot's move was submitted directly.
+/// * `InvalidChoice` - The move isn't light (1), defend (2) or heavy (3).
+/// * `BattleNotStarted` - The battle is still waiting for players or has ended.
+/// * `NotInBattle` - The player is not a participant of the battle.
+/// * `AlreadyMoved` - The player has already moved this round.
+/// * `ContractPaused` - The admin has paused new battles and forging.
+/// * `NoPotions` - The player has no potions left.
+/// * `NotRegistered` - The player hasn't been added with `add_player`.
+/// * `SpecialUsed` - The player already used their special this battle.
+/// * `NotInvited` - The battle's invite is for another player.
+/// * `StatCapReached` - The stat hit its maximum.
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum BattleError {
+    BotsDisabled = 1,
+    ForgeLimitReached = 2,
+    CannotBattleSelf = 3,
+    SeedMismatch = 4,
+    BotMoveNotAllowed = 5,
+    InvalidChoice = 6,
+    BattleNotStarted = 7,
+    NotInBattle = 8,
+    AlreadyMoved = 9,
+    ContractPaused = 10,
+    NoPotions = 11,
+    NotRegistered = 12,
+    SpecialUsed = 13,
+    NotInvited = 14,
+    StatCapReached = 15,
+}
+
+/// Struct representing the experience awarded for a win.
+///
+/// # Fields
+///
+/// * `pvp_win` - The experience awarded for beating another player.
+/// * `bot_win` - The experience awarded for beating the bot.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RewardConfig {
+    pub pvp_win: u32,
+    pub bot_win: u32,
+}
+
+/// Struct representing the statistics a player has without a sword.
+///
+/// # Fields
+///
+/// * `health` - The player's base health.
+/// * `attack` - The player's base attack.
+/// * `defense` - The player's base defense.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BaseStats {
+    pub health: u32,
+    pub attack: u32,
+    pub defense: u32,
 }
 
 /// Struct representing player statistics.
@@ -34,7 +248,18 @@ pub enum DataKey {
 /// * `health` - The health of the player.
 /// * `attack` - The attack of the player.
 /// * `defense` - The defense of the player.
-/// * `in_battle` - A boolean indicating whether the player is in a battle.
+/// * `base_health`, `base_attack`, `base_defense` - The stats without a sword.
+/// * `in_battle` - Whether the player is in a battle.
+/// * `rating` - The Elo rating of the player.
+/// * `peak_rating` - The player's highest Elo rating.
+/// * `wins`, `losses`, `draws` - The battles the player has won, lost and drawn.
+/// * `streak` - Consecutive wins (positive) or losses (negative).
+/// * `experience` - The experience the player has earned.
+/// * `level` - The level the player's experience has earned them.
+/// * `first_win_at` - When the player first won, if they have.
+/// * `durability` - The battles left before the player's sword breaks.
+/// * `total_damage_taken` - The health lost in all battles.
+/// * `current_battle` - The battle the player is in, or an empty symbol.
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct PlayerStat {
@@ -43,8 +268,23 @@ pub struct PlayerStat {
     pub health: u32,
     pub attack: u32,
     pub defense: u32,
+    pub base_health: u32,
+    pub base_attack: u32,
+    pub base_defense: u32,
     pub in_battle: bool,
     pub has_sword: bool,
+    pub rating: u32,
+    pub peak_rating: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    pub streak: i32,
+    pub experience: u32,
+    pub level: u32,
+    pub first_win_at: OptionalTimestamp,
+    pub durability: u32,
+    pub total_damage_taken: u64,
+    pub current_battle: Symbol,
 }
 
 /// Struct representing a battle.
@@ -55,7 +295,18 @@ pub struct PlayerStat {
 /// * `name` - The name of the battle.
 /// * `players` - The players in the battle.
 /// * `moves` - The moves made by the players in the battle.
-/// * `winner` - The winner of the battle.
+/// * `winner` - The winner of the battle, once it has one.
+/// * `last_move_ts` - The timestamp of the start or latest move.
+/// * `is_draw` - Whether the battle ended without a winner.
+/// * `result` - The outcome of the battle, or `Ongoing` until it ends.
+/// * `rounds` - The resolved rounds of the battle, oldest first.
+/// * `round_count` - The number of rounds resolved so far.
+/// * `first_mover` - Who moved first this round, if anyone has.
+/// * `specials` - Who used their special, and whether it's still pending.
+/// * `invited_at` - The ledger the invite was sent at, if any.
+/// * `max_players` - The number of players the battle starts with.
+/// * `seed_commit` - The SHA-256 of the creator's seed, or all zeros.
+/// * `revealed_seed` - The creator's seed once revealed.
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Battle {
@@ -64,7 +315,234 @@ pub struct Battle {
     pub players: Map<Address, u64>,
     pub moves: Map<Address, u64>,
     pub turns: u64,
-    pub winner: Address,
+    pub winner: OptionalAddress,
+    pub last_move_ts: u64,
+    pub is_draw: bool,
+    pub result: BattleResult,
+    pub rounds: Vec<Round>,
+    pub round_count: u64,
+    pub first_mover: OptionalAddress,
+    pub specials: Map<Address, bool>,
+    pub invited_at: OptionalLedger,
+    pub max_players: u32,
+    pub seed_commit: BytesN<32>,
+    pub revealed_seed: u64,
+}
+
+/// Struct representing a resolved round of a battle.
+///
+/// # Fields
+///
+/// * `moves` - The move each player made in the round.
+/// * `health` - Each player's health after the round. A player knocked out
+///   in the round is recorded with 0 health.
+/// * `dealt` - The damage each player dealt.
+/// * `taken` - The health each player lost.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Round {
+    pub moves: Map<Address, u64>,
+    pub health: Map<Address, u32>,
+    pub dealt: Map<Address, u32>,
+    pub taken: Map<Address, u32>,
+}
+
+/// Struct representing a pre-fight comparison of a battle's two players.
+///
+/// # Fields
+///
+/// * `player_a` - The player who created the battle.
+/// * `player_b` - The player who joined the battle.
+/// * `a_effective` - The current statistics of player A.
+/// * `b_effective` - The current statistics of player B.
+/// * `a_win_pct` - A rough estimate, in percent, of player A's chance to win.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MatchPreview {
+    pub player_a: Address,
+    pub player_b: Address,
+    pub a_effective: PlayerStat,
+    pub b_effective: PlayerStat,
+    pub a_win_pct: u32,
+}
+
+/// Struct representing a two-player battle with its maps flattened, for
+/// clients that can't easily decode `Battle`.
+///
+/// # Fields
+///
+/// * `name` - The name of the battle.
+/// * `status` - The status of the battle.
+/// * `player_a` - The player who created the battle, if still in it.
+/// * `move_a` - Player A's move this round, or 0 before they move.
+/// * `player_b` - The player who joined the battle, if still in it.
+/// * `move_b` - Player B's move this round, or 0 before they move.
+/// * `winner` - The winner of the battle, once it has one.
+/// * `round` - The number of rounds resolved so far.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FlatBattle {
+    pub name: Symbol,
+    pub status: u64,
+    pub player_a: OptionalAddress,
+    pub move_a: u64,
+    pub player_b: OptionalAddress,
+    pub move_b: u64,
+    pub winner: OptionalAddress,
+    pub round: u64,
+}
+
+/// Struct describing an ended two-player battle in the battle log.
+///
+/// # Fields
+///
+/// * `name` - The name of the battle.
+/// * `player_a` - The first player to join who was left at the end.
+/// * `player_b` - The other player left at the end.
+/// * `winner` - The winner of the battle, or `None` after a draw.
+/// * `is_draw` - Whether the battle ended without a winner.
+/// * `ended_at` - The ledger timestamp the battle ended at.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BattleLogEntry {
+    pub name: Symbol,
+    pub player_a: Address,
+    pub player_b: Address,
+    pub winner: OptionalAddress,
+    pub is_draw: bool,
+    pub ended_at: u64,
+}
+
+/// Struct describing a sword a player holds.
+///
+/// # Fields
+///
+/// * `token_id` - The id assigned to the sword when it was minted.
+/// * `class` - The sword class.
+/// * `metadata` - The sword's token metadata.
+/// * `health_bonus` - The health the sword adds to its wielder.
+/// * `attack_bonus` - The attack the sword adds to its wielder.
+/// * `defense_bonus` - The defense the sword adds to its wielder.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SwordInfo {
+    pub token_id: u64,
+    pub class: u32,
+    pub metadata: TokenMetadata,
+    pub health_bonus: i32,
+    pub attack_bonus: i32,
+    pub defense_bonus: i32,
+}
+
+/// Enum representing the outcome of a battle.
+///
+/// # Variants
+///
+/// * `Ongoing` - The battle hasn't ended yet.
+/// * `Player1Win` - The player who created the battle won.
+/// * `Player2Win` - Another player won.
+/// * `Draw` - The battle ended without a winner.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BattleResult {
+    Ongoing,
+    Player1Win,
+    Player2Win,
+    Draw,
+}
+
+/// Enum representing an address that may not be set. Contract types can't
+/// hold `Option` fields in this SDK, so optional addresses use this instead.
+///
+/// # Variants
+///
+/// * `None` - No address is set.
+/// * `Some` - The address that is set.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OptionalAddress {
+    None,
+    Some(Address),
+}
+
+impl From<Option<Address>> for OptionalAddress {
+    fn from(address: Option<Address>) -> Self {
+        match address {
+            Some(address) => OptionalAddress::Some(address),
+            None => OptionalAddress::None,
+        }
+    }
+}
+
+impl From<OptionalAddress> for Option<Address> {
+    fn from(address: OptionalAddress) -> Self {
+        match address {
+            OptionalAddress::Some(address) => Some(address),
+            OptionalAddress::None => None,
+        }
+    }
+}
+
+/// Enum representing a ledger sequence number that may not be set.
+///
+/// # Variants
+///
+/// * `None` - No ledger is set.
+/// * `Some` - The ledger sequence number that is set.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OptionalLedger {
+    None,
+    Some(u32),
+}
+
+impl From<Option<u32>> for OptionalLedger {
+    fn from(ledger: Option<u32>) -> Self {
+        match ledger {
+            Some(ledger) => OptionalLedger::Some(ledger),
+            None => OptionalLedger::None,
+        }
+    }
+}
+
+impl From<OptionalLedger> for Option<u32> {
+    fn from(ledger: OptionalLedger) -> Self {
+        match ledger {
+            OptionalLedger::Some(ledger) => Some(ledger),
+            OptionalLedger::None => None,
+        }
+    }
+}
+
+/// Enum representing a ledger timestamp that may not be set.
+///
+/// # Variants
+///
+/// * `None` - No timestamp is set.
+/// * `Some` - The timestamp that is set.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OptionalTimestamp {
+    None,
+    Some(u64),
+}
+
+impl From<Option<u64>> for OptionalTimestamp {
+    fn from(timestamp: Option<u64>) -> Self {
+        match timestamp {
+            Some(timestamp) => OptionalTimestamp::Some(timestamp),
+            None => OptionalTimestamp::None,
+        }
+    }
+}
+
+impl From<OptionalTimestamp> for Option<u64> {
+    fn from(timestamp: OptionalTimestamp) -> Self {
+        match timestamp {
+            OptionalTimestamp::Some(timestamp) => Some(timestamp),
+            OptionalTimestamp::None => None,
+        }
+    }
 }
 
 /// Enum representing battle statuses.
@@ -104,541 +582,3772 @@ pub struct BattleContract;
 /// Implementation of the BattleContract.
 #[contractimpl]
 impl BattleContract {
-    /// Adds a player to the battle.
+    /// Initializes the contract with an administrator, the sword contract
+    /// and the token battles are wagered in. From then on every sword forged,
+    /// melted or fused is also minted or melted on the sword contract, which
+    /// must have this contract as its minter.
     ///
     /// # Arguments
     ///
     /// * `env` - The contract execution environment.
-    /// * `user` - The address of the player to add.
-    pub fn add_player(env: Env, user: Address) {
-        user.require_auth();
-        env.storage().instance().set(
-            &DataKey::Player(user.clone()),
-            &PlayerStat {
-                player_address: user.clone(),
-                sword_class: 0,
-                health: 100,
-                attack: 10,
-                defense: 10,
-                in_battle: false,
-                has_sword: false,
-            },
+    /// * `admin` - The address of the administrator.
+    /// * `sword_contract` - The address of the sword NFT contract.
+    /// * `wager_token` - The address of the token contract used for wagers.
+    pub fn initialize(env: Env, admin: Address, sword_contract: Address, wager_token: Address) {
+        assert!(!has_administrator(&env), "Already initialized");
+        assert!(
+            sword_contract != env.current_contract_address(),
+            "The sword contract must be a separate contract"
         );
+        admin.require_auth();
+        write_administrator(&env, &admin);
+        env.storage()
+            .instance()
+            .set(&DataKey::SwordContract, &sword_contract);
+        env.storage()
+            .instance()
+            .set(&DataKey::WagerToken, &wager_token);
+        env.storage()
+            .instance()
+            .set(&DataKey::SchemaVersion, &CONTRACT_VERSION);
+        env.storage()
+            .instance()
+            .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
 
-        let mut players: Vec<Address> = Self::get_players(env.clone());
-        players.push_back(user.clone());
-        Self::set_players(env.clone(), players);
+    /// Gets the administrator of the contract.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    pub fn get_admin(env: Env) -> Address {
+        assert!(has_administrator(&env), "Not initialized");
+        read_administrator(&env)
     }
 
-    /// Sets the player statistics for a given player.
+    /// Gets the version of the contract's storage layout, so clients can
+    /// check they understand the data it returns.
     ///
     /// # Arguments
     ///
     /// * `env` - The contract execution environment.
-    /// * `user` - The address of the player.
-    /// * `player_stat` - The PlayerStat struct containing the player's statistics.
-    fn set_player_stats(env: Env, user: Address, player_stat: PlayerStat) -> Result<(), Error> {
+    pub fn version(_env: Env) -> u32 {
+        CONTRACT_VERSION
+    }
+
+    /// Rewrites every stored player and battle in the current `PlayerStat`
+    /// and `Battle` layouts, filling fields added since they were stored with
+    /// their defaults. Storage already migrated is left alone. Admin only.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `admin` - The address of the administrator.
+    pub fn migrate_players(env: Env, admin: Address) {
+        assert!(has_administrator(&env), "Not initialized");
+        assert!(
+            admin == read_administrator(&env),
+            "Only the admin can migrate"
+        );
+        admin.require_auth();
+        let version: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::SchemaVersion)
+            .unwrap_or(0);
+        if version >= CONTRACT_VERSION {
+            return;
+        }
+
+        for player in Self::get_players(env.clone()).iter() {
+            let key = DataKey::Player(player.clone());
+            let Some(raw) = env.storage().instance().get::<_, Map<Symbol, Val>>(&key) else {
+                continue;
+            };
+            let stat = Self::upgrade_player_stat(&env, player.clone(), raw);
+            let _ = Self::set_player_stats(env.clone(), player, stat);
+        }
+
+        let mut names = Self::get_battles(env.clone());
+        names.append(&Self::get_battle_archive(env.clone()));
+        for name in names.iter() {
+            let key = DataKey::Battle(name.clone());
+            let Some(raw) = env.storage().instance().get::<_, Map<Symbol, Val>>(&key) else {
+                continue;
+            };
+            let battle = Self::upgrade_battle(&env, name.clone(), raw);
+            let _ = Self::set_battle(env.clone(), name, battle);
+        }
         env.storage()
             .instance()
-            .set(&DataKey::Player(user), &player_stat);
-        env.storage().instance().bump(100, 100);
-        Ok(())
+            .set(&DataKey::SchemaVersion, &CONTRACT_VERSION);
     }
 
-    /// Gets the player statistics for a given player.
+    /// A private function to read a stored player of any earlier layout.
+    /// Missing fields get the defaults a new player starts with, and the
+    /// player's own statistics are their stored ones without the sword.
     ///
     /// # Arguments
     ///
     /// * `env` - The contract execution environment.
     /// * `user` - The address of the player.
+    /// * `raw` - The stored player, field by field.
+    fn upgrade_player_stat(env: &Env, user: Address, raw: Map<Symbol, Val>) -> PlayerStat {
+        let base = Self::get_base_stats(env.clone());
+        let sword_class = Self::stored_field(env, &raw, "sword_class").unwrap_or(0);
+        let has_sword = Self::stored_field(env, &raw, "has_sword").unwrap_or(false);
+        let health = Self::stored_field(env, &raw, "health").unwrap_or(base.health);
+        let attack = Self::stored_field(env, &raw, "attack").unwrap_or(base.attack);
+        let defense = Self::stored_field(env, &raw, "defense").unwrap_or(base.defense);
+        let (_, attack_bonus, defense_bonus) = if has_sword {
+            Self::sword_bonus(sword_class)
+        } else {
+            (0, 0, 0)
+        };
+        let rating = Self::stored_field(env, &raw, "rating").unwrap_or(STARTING_RATING);
+        PlayerStat {
+            player_address: user,
+            sword_class,
+            health,
+            attack,
+            defense,
+            base_health: Self::stored_field(env, &raw, "base_health").unwrap_or(base.health),
+            base_attack: Self::stored_field(env, &raw, "base_attack")
+                .unwrap_or(attack.saturating_add_signed(-attack_bonus)),
+            base_defense: Self::stored_field(env, &raw, "base_defense")
+                .unwrap_or(defense.saturating_add_signed(-defense_bonus)),
+            in_battle: Self::stored_field(env, &raw, "in_battle").unwrap_or(false),
+            has_sword,
+            rating,
+            peak_rating: Self::stored_field(env, &raw, "peak_rating").unwrap_or(rating),
+            wins: Self::stored_field(env, &raw, "wins").unwrap_or(0),
+            losses: Self::stored_field(env, &raw, "losses").unwrap_or(0),
+            draws: Self::stored_field(env, &raw, "draws").unwrap_or(0),
+            streak: Self::stored_field(env, &raw, "streak").unwrap_or(0),
+            experience: Self::stored_field(env, &raw, "experience").unwrap_or(0),
+            level: Self::stored_field(env, &raw, "level").unwrap_or(1),
+            // Before version 6 a player without a win had a timestamp of 0
+            first_win_at: Self::stored_field(env, &raw, "first_win_at").unwrap_or_else(|| {
+                Self::stored_field::<u64>(env, &raw, "first_win_at")
+                    .filter(|timestamp| *timestamp != 0)
+                    .into()
+            }),
+            durability: Self::stored_field(env, &raw, "durability").unwrap_or(if has_sword {
+                Self::max_durability(sword_class)
+            } else {
+                0
+            }),
+            total_damage_taken: Self::stored_field(env, &raw, "total_damage_taken").unwrap_or(0),
+            current_battle: Self::stored_field(env, &raw, "current_battle")
+                .unwrap_or(Symbol::new(env, "")),
+        }
+    }
+
+    /// A private function to read a stored battle of any earlier layout.
+    /// Missing fields get the defaults a new battle starts with, and the
+    /// round count is the number of rounds it recorded.
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// A PlayerStat struct containing the player's statistics.
-    pub fn get_player_stats(env: Env, user: Address) -> PlayerStat {
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    /// * `raw` - The stored battle, field by field.
+    fn upgrade_battle(env: &Env, name: Symbol, raw: Map<Symbol, Val>) -> Battle {
+        let contract_id = env.current_contract_address();
+        let rounds: Vec<Round> = Self::stored_field(env, &raw, "rounds").unwrap_or(Vec::new(env));
+        let battle_status = Self::stored_field(env, &raw, "battle_status").unwrap_or(0);
+        let is_draw = Self::stored_field(env, &raw, "is_draw").unwrap_or(false);
+        // Before version 6 the contract's address stood in for a missing
+        // winner or first mover, and ledger 0 for a missing invite
+        let has_winner = battle_status == BattleStatus::Ended as u64 && !is_draw;
+        Battle {
+            battle_status,
+            name,
+            players: Self::stored_field(env, &raw, "players").unwrap_or(Map::new(env)),
+            moves: Self::stored_field(env, &raw, "moves").unwrap_or(Map::new(env)),
+            turns: Self::stored_field(env, &raw, "turns").unwrap_or(0),
+            winner: Self::stored_field(env, &raw, "winner").unwrap_or_else(|| {
+                Self::stored_field::<Address>(env, &raw, "winner")
+                    .filter(|_| has_winner)
+                    .into()
+            }),
+            last_move_ts: Self::stored_field(env, &raw, "last_move_ts").unwrap_or(0),
+            is_draw,
+            result: Self::stored_field(env, &raw, "result").unwrap_or(BattleResult::Ongoing),
+            round_count: Self::stored_field(env, &raw, "round_count")
+                .unwrap_or(rounds.len() as u64),
+            rounds,
+            first_mover: Self::stored_field(env, &raw, "first_mover").unwrap_or_else(|| {
+                Self::stored_field::<Address>(env, &raw, "first_mover")
+                    .filter(|mover| *mover != contract_id)
+                    .into()
+            }),
+            specials: Self::stored_field(env, &raw, "specials").unwrap_or(Map::new(env)),
+            invited_at: Self::stored_field(env, &raw, "invited_at").unwrap_or_else(|| {
+                Self::stored_field::<u32>(env, &raw, "invited_at")
+                    .filter(|ledger| *ledger != 0)
+                    .into()
+            }),
+            max_players: Self::stored_field(env, &raw, "max_players").unwrap_or(2),
+            seed_commit: Self::stored_field(env, &raw, "seed_commit")
+                .unwrap_or(BytesN::from_array(env, &[0; 32])),
+            revealed_seed: Self::stored_field(env, &raw, "revealed_seed").unwrap_or(0),
+        }
+    }
+
+    /// A private function to read one field of a stored record, or `None` if
+    /// the record's layout predates it.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `raw` - The stored record, field by field.
+    /// * `name` - The name of the field.
+    fn stored_field<T: TryFromVal<Env, Val>>(
+        env: &Env,
+        raw: &Map<Symbol, Val>,
+        name: &str,
+    ) -> Option<T> {
+        let value = raw.get(Symbol::new(env, name))?;
+        T::try_from_val(env, &value).ok()
+    }
+
+    /// Extends the lifetime of the contract's instance storage, which holds
+    /// every player and battle, so it doesn't expire. Admin only.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `admin` - The address of the administrator.
+    /// * `ledgers_to_live` - The number of ledgers the storage should live for.
+    pub fn bump_ttl(env: Env, admin: Address, ledgers_to_live: u32) {
+        assert!(has_administrator(&env), "Not initialized");
+        assert!(admin == read_administrator(&env), "Only the admin can bump");
+        admin.require_auth();
         env.storage()
             .instance()
-            .get(&DataKey::Player(user))
-            .unwrap_or(PlayerStat {
-                player_address: env.current_contract_address(),
-                sword_class: 0,
-                health: 0,
-                attack: 0,
-                defense: 0,
-                in_battle: false,
-                has_sword: false,
-            })
+            .bump(ledgers_to_live, ledgers_to_live);
     }
 
-    /// Sets the list of players in the battle.
+    /// Gets the address of the sword NFT contract.
     ///
     /// # Arguments
     ///
     /// * `env` - The contract execution environment.
-    /// * `players` - The Vec<Address> containing the addresses of the players.
-    fn set_players(env: Env, players: Vec<Address>) {
-        env.storage().instance().set(&DataKey::Players, &players);
-        env.storage().instance().bump(100, 100);
+    pub fn get_sword_contract(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::SwordContract)
+            .expect("Not initialized")
     }
 
-    /// Gets the list of players in the battle.
+    /// Gets the address of the token battles are wagered in.
     ///
     /// # Arguments
     ///
     /// * `env` - The contract execution environment.
+    pub fn get_wager_token(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::WagerToken)
+            .expect("Not initialized")
+    }
+
+    /// Gets the amount each player of a battle has wagered, or 0 if the
+    /// battle isn't wagered.
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// A Vec<Address> containing the addresses of the players.
-    pub fn get_players(env: Env) -> Vec<Address> {
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    pub fn get_wager(env: Env, name: Symbol) -> i128 {
         env.storage()
             .instance()
-            .get(&DataKey::Players)
-            .unwrap_or(Vec::new(&env))
+            .get(&DataKey::Wager(name))
+            .unwrap_or(0)
     }
 
-    // Classes:
-    // Longsword:
-    // Health: +8 HP - Wielding a longsword grants the player an additional 10 hit points, providing extra survivability.
-    // Attack: +4 ATK - The longsword's extended reach and balanced design grant a 5-point increase in attack power.
-    // Defense: +3 DEF - The longsword's hilt and guard offer decent protection, adding 3 points to the player's defense.
+    /// Gets the contract's balance of each of the given tokens.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `tokens` - The addresses of the token contracts to query.
+    ///
+    /// # Returns
+    ///
+    /// A Vec of (token, balance) pairs in the order the tokens were given.
+    pub fn treasury_summary(env: Env, tokens: Vec<Address>) -> Vec<(Address, i128)> {
+        let contract = env.current_contract_address();
+        let mut summary = Vec::new(&env);
+        for token in tokens.iter() {
+            let balance = token::Client::new(&env, &token).balance(&contract);
+            summary.push_back((token, balance));
+        }
+        summary
+    }
+
+    /// Sets the experience awarded for PvP and bot wins. Admin only.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `admin` - The address of the administrator.
+    /// * `pvp_win` - The experience awarded for beating another player.
+    /// * `bot_win` - The experience awarded for beating the bot.
+    pub fn set_rewards(env: Env, admin: Address, pvp_win: u32, bot_win: u32) {
+        assert!(has_administrator(&env), "Not initialized");
+        assert!(
+            admin == read_administrator(&env),
+            "Only the admin can change rewards"
+        );
+        admin.require_auth();
+        env.storage()
+            .instance()
+            .set(&DataKey::Rewards, &RewardConfig { pvp_win, bot_win });
+        env.storage()
+            .instance()
+            .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    /// Gets the experience awarded for PvP and bot wins.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    pub fn get_rewards(env: Env) -> RewardConfig {
+        env.storage()
+            .instance()
+            .get(&DataKey::Rewards)
+            .unwrap_or(RewardConfig {
+                pvp_win: DEFAULT_PVP_WIN_REWARD,
+                bot_win: DEFAULT_BOT_WIN_REWARD,
+            })
+    }
+
+    /// Sets the statistics new players start with. Admin only. Health must be
+    /// between 1 and `MAX_BASE_HEALTH`, and attack and defense may not exceed
+    /// `MAX_ATTACK` and `MAX_DEFENSE`.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `admin` - The address of the administrator.
+    /// * `health` - The base health.
+    /// * `attack` - The base attack.
+    /// * `defense` - The base defense.
+    pub fn set_base_stats(env: Env, admin: Address, health: u32, attack: u32, defense: u32) {
+        assert!(has_administrator(&env), "Not initialized");
+        assert!(
+            admin == read_administrator(&env),
+            "Only the admin can change base stats"
+        );
+        admin.require_auth();
+        assert!(
+            (1..=MAX_BASE_HEALTH).contains(&health),
+            "Invalid base health"
+        );
+        assert!(attack <= MAX_ATTACK, "Base attack too high");
+        assert!(defense <= MAX_DEFENSE, "Base defense too high");
+        env.storage().instance().set(
+            &DataKey::BaseStats,
+            &BaseStats {
+                health,
+                attack,
+                defense,
+            },
+        );
+        env.storage()
+            .instance()
+            .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    /// Gets the statistics new players start with.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    pub fn get_base_stats(env: Env) -> BaseStats {
+        env.storage()
+            .instance()
+            .get(&DataKey::BaseStats)
+            .unwrap_or(BaseStats {
+                health: MAX_HEALTH,
+                attack: DEFAULT_BASE_ATTACK,
+                defense: DEFAULT_BASE_DEFENSE,
+            })
+    }
+
+    /// Sets the percent of their attack players lose for every round a battle
+    /// has lasted, so long battles trend toward a resolution. Admin only.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `admin` - The address of the administrator.
+    /// * `percent` - The percent of attack lost per round, up to 100.
+    pub fn set_attack_decay(env: Env, admin: Address, percent: u32) {
+        assert!(has_administrator(&env), "Not initialized");
+        assert!(
+            admin == read_administrator(&env),
+            "Only the admin can change attack decay"
+        );
+        admin.require_auth();
+        assert!(percent <= 100, "Invalid decay percent");
+        env.storage()
+            .instance()
+            .set(&DataKey::AttackDecay, &percent);
+        env.storage()
+            .instance()
+            .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    /// Gets the percent of attack lost per round. No decay by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    pub fn get_attack_decay(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::AttackDecay)
+            .unwrap_or(0)
+    }
+
+    /// Turns bot battles on or off. Admin only.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `admin` - The address of the administrator.
+    /// * `enabled` - Whether players may battle the bot.
+    pub fn set_bots_enabled(env: Env, admin: Address, enabled: bool) {
+        assert!(has_administrator(&env), "Not initialized");
+        assert!(
+            admin == read_administrator(&env),
+            "Only the admin can toggle bots"
+        );
+        admin.require_auth();
+        env.storage()
+            .instance()
+            .set(&DataKey::BotsEnabled, &enabled);
+        env.storage()
+            .instance()
+            .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    /// Gets whether bot battles are allowed. Bots are enabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    pub fn get_bots_enabled(env: Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&DataKey::BotsEnabled)
+            .unwrap_or(true)
+    }
+
+    /// Pauses or unpauses the contract. While paused, no battles can be
+    /// created or joined and no swords forged, but battles in progress can
+    /// still be fought to the end. Admin only.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `admin` - The address of the administrator.
+    /// * `paused` - Whether the contract is paused.
+    pub fn set_paused(env: Env, admin: Address, paused: bool) {
+        assert!(has_administrator(&env), "Not initialized");
+        assert!(
+            admin == read_administrator(&env),
+            "Only the admin can pause"
+        );
+        admin.require_auth();
+        env.storage().instance().set(&DataKey::Paused, &paused);
+        env.storage()
+            .instance()
+            .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    /// Resets a player to their base statistics and takes them out of any
+    /// battle, for testing. Unlike `melt_blade`, clearing the sword doesn't
+    /// burn its NFT. Admin only.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `admin` - The address of the administrator.
+    /// * `user` - The address of the player.
+    /// * `keep_sword` - Whether the player keeps wielding their sword.
+    pub fn reset_player(env: Env, admin: Address, user: Address, keep_sword: bool) {
+        assert!(has_administrator(&env), "Not initialized");
+        assert!(
+            admin == read_administrator(&env),
+            "Only the admin can reset players"
+        );
+        admin.require_auth();
+        assert!(
+            env.storage().instance().has(&DataKey::Player(user.clone())),
+            "Player not registered"
+        );
+
+        let mut player = Self::get_player_stats(env.clone(), user.clone());
+        if !keep_sword {
+            player.has_sword = false;
+            player.sword_class = 0;
+            player.durability = 0;
+        }
+        player.in_battle = false;
+        player.current_battle = Symbol::new(&env, "");
+        Self::apply_sword(&mut player);
+        let _ = Self::set_player_stats(env, user, player);
+    }
+
+    /// Gets whether the contract is paused. The contract is unpaused by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    pub fn get_paused(env: Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&DataKey::Paused)
+            .unwrap_or(false)
+    }
+
+    /// Sets the number of spectators a battle allows. Admin only.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `admin` - The address of the administrator.
+    /// * `max_spectators` - The number of spectators a battle allows.
+    pub fn set_max_spectators(env: Env, admin: Address, max_spectators: u32) {
+        assert!(has_administrator(&env), "Not initialized");
+        assert!(
+            admin == read_administrator(&env),
+            "Only the admin can change the spectator cap"
+        );
+        admin.require_auth();
+        env.storage()
+            .instance()
+            .set(&DataKey::MaxSpectators, &max_spectators);
+        env.storage()
+            .instance()
+            .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    /// Gets the number of spectators a battle allows.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    pub fn get_max_spectators(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::MaxSpectators)
+            .unwrap_or(DEFAULT_MAX_SPECTATORS)
+    }
+
+    /// Sets the seconds a player has to make their move before their opponent
+    /// can claim the win. Admin only.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `admin` - The address of the administrator.
+    /// * `seconds` - The seconds a player has to make their move.
+    pub fn set_move_timeout(env: Env, admin: Address, seconds: u64) {
+        assert!(has_administrator(&env), "Not initialized");
+        assert!(
+            admin == read_administrator(&env),
+            "Only the admin can change the move timeout"
+        );
+        admin.require_auth();
+        assert!(seconds > 0, "Move timeout must be positive");
+        env.storage()
+            .instance()
+            .set(&DataKey::MoveTimeout, &seconds);
+        env.storage()
+            .instance()
+            .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    /// Gets the seconds a player has to make their move.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    pub fn get_move_timeout(env: Env) -> u64 {
+        env.storage()
+            .instance()
+            .get(&DataKey::MoveTimeout)
+            .unwrap_or(MOVE_TIMEOUT)
+    }
+
+    /// Caps how many swords of a class a single player can ever forge. Admin only.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `admin` - The address of the administrator.
+    /// * `class` - The sword class.
+    /// * `limit` - The number of swords of the class a player may forge.
+    pub fn set_forge_limit(env: Env, admin: Address, class: u32, limit: u32) {
+        assert!(has_administrator(&env), "Not initialized");
+        assert!(
+            admin == read_administrator(&env),
+            "Only the admin can change forge limits"
+        );
+        admin.require_auth();
+        env.storage()
+            .instance()
+            .set(&DataKey::ForgeLimit(class), &limit);
+        env.storage()
+            .instance()
+            .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    /// Gets how many swords of a class a single player can ever forge, or
+    /// `None` if the class is unlimited.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `class` - The sword class.
+    pub fn get_forge_limit(env: Env, class: u32) -> Option<u32> {
+        env.storage().instance().get(&DataKey::ForgeLimit(class))
+    }
+
+    /// Gets how many swords of a class have been forged by all players. Melting
+    /// a sword doesn't lower the count.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `class` - The sword class.
+    pub fn get_forge_count(env: Env, class: u32) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::ForgeTotal(class))
+            .unwrap_or(0)
+    }
+
+    /// Adds a player to the battle. Adding a registered player again does nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player to add.
+    pub fn add_player(env: Env, user: Address) {
+        user.require_auth();
+        if env.storage().instance().has(&DataKey::Player(user.clone())) {
+            return;
+        }
+        let base = Self::get_base_stats(env.clone());
+        env.storage().instance().set(
+            &DataKey::Player(user.clone()),
+            &PlayerStat {
+                player_address: user.clone(),
+                sword_class: 0,
+                health: base.health,
+                attack: base.attack,
+                defense: base.defense,
+                base_health: base.health,
+                base_attack: base.attack,
+                base_defense: base.defense,
+                in_battle: false,
+                has_sword: false,
+                rating: STARTING_RATING,
+                peak_rating: STARTING_RATING,
+                wins: 0,
+                losses: 0,
+                draws: 0,
+                streak: 0,
+                experience: 0,
+                level: 1,
+                first_win_at: OptionalTimestamp::None,
+                durability: 0,
+                total_damage_taken: 0,
+                current_battle: Symbol::new(&env, ""),
+            },
+        );
+
+        let mut players: Vec<Address> = Self::get_players(env.clone());
+        players.push_back(user.clone());
+        Self::set_players(env.clone(), players);
+    }
+
+    /// Adds several players at once. Players already registered are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `users` - The addresses of the players to add.
+    pub fn add_players(env: Env, users: Vec<Address>) {
+        for user in users.iter() {
+            Self::add_player(env.clone(), user);
+        }
+    }
+
+    /// Removes a player and their statistics from the registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player to remove.
+    pub fn remove_player(env: Env, user: Address) {
+        user.require_auth();
+        let player = Self::get_player_stats(env.clone(), user.clone());
+        assert!(!player.in_battle, "Can't leave while in battle");
+
+        env.storage()
+            .instance()
+            .remove(&DataKey::Player(user.clone()));
+        let mut players = Self::get_players(env.clone());
+        if let Some(index) = players.first_index_of(user) {
+            players.remove(index);
+        }
+        Self::set_players(env, players);
+    }
+
+    /// Sets the player statistics for a given player.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
+    /// * `player_stat` - The PlayerStat struct containing the player's statistics.
+    fn set_player_stats(env: Env, user: Address, player_stat: PlayerStat) -> Result<(), Error> {
+        env.storage()
+            .instance()
+            .set(&DataKey::Player(user), &player_stat);
+        env.storage()
+            .instance()
+            .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        Ok(())
+    }
+
+    /// Gets the player statistics for a given player.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
+    ///
+    /// # Returns
+    ///
+    /// A PlayerStat struct containing the player's statistics.
+    pub fn get_player_stats(env: Env, user: Address) -> PlayerStat {
+        env.storage()
+            .instance()
+            .get(&DataKey::Player(user))
+            .unwrap_or(PlayerStat {
+                player_address: env.current_contract_address(),
+                sword_class: 0,
+                health: 0,
+                attack: 0,
+                defense: 0,
+                base_health: 0,
+                base_attack: 0,
+                base_defense: 0,
+                in_battle: false,
+                has_sword: false,
+                rating: 0,
+                peak_rating: 0,
+                wins: 0,
+                losses: 0,
+                draws: 0,
+                streak: 0,
+                experience: 0,
+                level: 0,
+                first_win_at: OptionalTimestamp::None,
+                durability: 0,
+                total_damage_taken: 0,
+                current_battle: Symbol::new(&env, ""),
+            })
+    }
+
+    /// Sets the list of players in the battle.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `players` - The Vec<Address> containing the addresses of the players.
+    fn set_players(env: Env, players: Vec<Address>) {
+        env.storage().instance().set(&DataKey::Players, &players);
+        env.storage()
+            .instance()
+            .set(&DataKey::PlayerCount, &players.len());
+        env.storage()
+            .instance()
+            .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    /// Gets the list of players in the battle.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    ///
+    /// # Returns
+    ///
+    /// A Vec<Address> containing the addresses of the players.
+    pub fn get_players(env: Env) -> Vec<Address> {
+        env.storage()
+            .instance()
+            .get(&DataKey::Players)
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Gets the number of registered players without reading the player list.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    pub fn player_count(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::PlayerCount)
+            .unwrap_or(0)
+    }
+
+    /// Gets the statistics of every registered player, in registration order.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    fn get_all_player_stats(env: Env) -> Vec<PlayerStat> {
+        let mut stats = Vec::new(&env);
+        for player in Self::get_players(env.clone()).iter() {
+            stats.push_back(Self::get_player_stats(env.clone(), player));
+        }
+        stats
+    }
+
+    /// Gets the statistics of a page of registered players, in registration
+    /// order.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `start` - The index of the first player on the page.
+    /// * `count` - The number of players on the page, up to `MAX_PAGE_SIZE`.
+    ///
+    /// # Returns
+    ///
+    /// A Vec of PlayerStat, empty if `start` is past the last player.
+    pub fn get_player_stats_page(env: Env, start: u32, count: u32) -> Vec<PlayerStat> {
+        let players = Self::get_players(env.clone());
+        let start = start.min(players.len());
+        let end = start
+            .saturating_add(count.min(MAX_PAGE_SIZE))
+            .min(players.len());
+        let mut stats = Vec::new(&env);
+        for player in players.slice(start..end).iter() {
+            stats.push_back(Self::get_player_stats(env.clone(), player));
+        }
+        stats
+    }
+
+    /// Gets the highest Elo rating a player has ever reached.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
+    pub fn get_peak_rating(env: Env, user: Address) -> u32 {
+        Self::get_player_stats(env, user).peak_rating
+    }
+
+    /// Gets a player's current streak: consecutive wins are positive and
+    /// consecutive losses negative.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
+    pub fn get_streak(env: Env, user: Address) -> i32 {
+        Self::get_player_stats(env, user).streak
+    }
+
+    /// Gets the ledger timestamp of a player's first win, if they have won.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
+    pub fn get_first_win_at(env: Env, user: Address) -> Option<u64> {
+        Self::get_player_stats(env, user).first_win_at.into()
+    }
+
+    /// Gets the players with the most wins.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `limit` - The maximum number of players to return.
+    ///
+    /// # Returns
+    ///
+    /// A Vec of PlayerStat sorted by wins, highest first. Ties go to the player
+    /// with fewer losses.
+    pub fn get_leaderboard(env: Env, limit: u32) -> Vec<PlayerStat> {
+        let stats = Self::get_all_player_stats(env.clone());
+        top_players(&env, stats, limit, |stat| (stat.wins, Reverse(stat.losses)))
+    }
+
+    /// Gets the players with the highest Elo rating.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `top_n` - The maximum number of players to return.
+    ///
+    /// # Returns
+    ///
+    /// A Vec of (address, rating) pairs sorted by rating, highest first.
+    pub fn rating_leaderboard(env: Env, top_n: u32) -> Vec<(Address, u32)> {
+        let stats = Self::get_all_player_stats(env.clone());
+        let mut leaderboard = Vec::new(&env);
+        for stat in top_players(&env, stats, top_n, |stat| stat.rating).iter() {
+            leaderboard.push_back((stat.player_address, stat.rating));
+        }
+        leaderboard
+    }
+
+    // Classes:
+    // Longsword:
+    // Health: +8 HP - Wielding a longsword grants the player an additional 10 hit points, providing extra survivability.
+    // Attack: +4 ATK - The longsword's extended reach and balanced design grant a 5-point increase in attack power.
+    // Defense: +3 DEF - The longsword's hilt and guard offer decent protection, adding 3 points to the player's defense.
 
     // Sabre:
     // Health: -3 HP - The sabre's focus on speed over protection results in a slight 3 HP reduction.
     // Attack: +16 ATK - Sabres excel in fast and precise slashing attacks, providing a substantial 10-point boost to the player's attack power.
     // Defense: +2 DEF - Although not the most defensive option, the sabre's curved design and guard offer a 2-point increase in defense.
 
-    // Claymore:
-    // Health: +7 HP - Wielding a claymore grants the player an additional 7 hit points, enhancing their durability.
-    // Attack: +11 ATK - The claymore's size and sweeping strikes provide a significant 11-point boost to the player's attack power.
-    // Defense: -3 DEF - Due to its size and lack of a guard, the claymore reduces the player's defense by 3 points.
+    // Claymore:
+    // Health: +7 HP - Wielding a claymore grants the player an additional 7 hit points, enhancing their durability.
+    // Attack: +11 ATK - The claymore's size and sweeping strikes provide a significant 11-point boost to the player's attack power.
+    // Defense: -3 DEF - Due to its size and lack of a guard, the claymore reduces the player's defense by 3 points.
+
+    pub fn forge_blade(env: Env, to: Address, class: u32) -> Result<(), Error> {
+        to.require_auth();
+        if Self::get_paused(env.clone()) {
+            return Err(BattleError::ContractPaused.into());
+        }
+        let player_info = Self::get_player_stats(env.clone(), to.clone());
+        assert!(!player_info.has_sword, "must not own a sword");
+        assert!(!player_info.in_battle, "Can't forge blade while in battle");
+        assert!(
+            player_info.health == player_info.base_health,
+            "Must be at full health to forge a blade"
+        );
+        assert!((1..=3).contains(&class), "Invalid sword class");
+
+        let forge_count_key = DataKey::ForgeCount(to.clone(), class);
+        let forged: u32 = env.storage().instance().get(&forge_count_key).unwrap_or(0);
+        if let Some(limit) = Self::get_forge_limit(env.clone(), class) {
+            if forged >= limit {
+                return Err(BattleError::ForgeLimitReached.into());
+            }
+        }
+        env.storage()
+            .instance()
+            .set(&forge_count_key, &(forged + 1));
+        let total = Self::get_forge_count(env.clone(), class);
+        env.storage()
+            .instance()
+            .set(&DataKey::ForgeTotal(class), &(total + 1));
+
+        let mut player = Self::get_player_stats(env.clone(), to.clone());
+        player.sword_class = class;
+        player.has_sword = true;
+        player.durability = Self::max_durability(class);
+        Self::apply_sword(&mut player);
+        Self::mint_sword(&env, &to, class)?;
+        Self::set_player_stats(env.clone(), to.clone(), player)?;
+        events::sword_forged(&env, to, class);
+        Ok(())
+    }
+
+    /// Gets the token URI of the sword a player wields, or an empty string if
+    /// they don't wield one.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
+    pub fn get_equipped_sword_uri(env: Env, user: Address) -> String {
+        let player = Self::get_player_stats(env.clone(), user);
+        if !player.has_sword {
+            return String::from_slice(&env, "");
+        }
+        SwordContract::get_token_metadata(env, player.sword_class).token_uri
+    }
+
+    /// Lists the swords a player holds, with their metadata and stat bonuses.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `owner` - The address of the player.
+    pub fn inventory(env: Env, owner: Address) -> Vec<SwordInfo> {
+        let mut swords = Vec::new(&env);
+        for (token_id, class) in read_owned_tokens(&env, owner).iter() {
+            let (health_bonus, attack_bonus, defense_bonus) = Self::sword_bonus(class);
+            swords.push_back(SwordInfo {
+                token_id,
+                class,
+                metadata: SwordContract::get_token_metadata(env.clone(), class),
+                health_bonus,
+                attack_bonus,
+                defense_bonus,
+            });
+        }
+        swords
+    }
+
+    /// Gets the `(health, attack, defense)` bonuses a sword class grants. These
+    /// are exactly what `forge_blade` adds and `melt_blade` takes away. Note
+    /// that the Sabre's health bonus is negative.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `class` - The sword class.
+    pub fn get_sword_bonus(_env: Env, class: u32) -> (i32, i32, i32) {
+        Self::sword_bonus(class)
+    }
+
+    /// The `(health, attack, defense)` bonuses a sword class grants.
+    ///
+    /// # Arguments
+    ///
+    /// * `class` - The sword class.
+    fn sword_bonus(class: u32) -> (i32, i32, i32) {
+        match class {
+            2 => (-3, 16, 2),
+            3 => (7, 11, -3),
+            4..=6 => {
+                let (health, attack, defense) = Self::sword_bonus(class - ENHANCED_CLASS_OFFSET);
+                (health * 2, attack * 2, defense * 2)
+            }
+            _ => (8, 4, 3),
+        }
+    }
+
+    /// A player's statistics with their sword: their own statistics plus the
+    /// bonuses of the sword they wield, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `stat` - The statistics of the player.
+    fn effective_stats(stat: &PlayerStat) -> BaseStats {
+        let (health, attack, defense) = if stat.has_sword {
+            Self::sword_bonus(stat.sword_class)
+        } else {
+            (0, 0, 0)
+        };
+        BaseStats {
+            health: stat.base_health.saturating_add_signed(health),
+            attack: stat.base_attack.saturating_add_signed(attack),
+            defense: stat.base_defense.saturating_add_signed(defense),
+        }
+    }
+
+    /// Updates a player's statistics after their sword changed. Only the
+    /// sword's bonuses are added or removed; the player's own statistics are
+    /// left alone. Outside of battle, players are always at full health.
+    ///
+    /// # Arguments
+    ///
+    /// * `stat` - The statistics of the player.
+    fn apply_sword(stat: &mut PlayerStat) {
+        let effective = Self::effective_stats(stat);
+        stat.health = effective.health;
+        stat.attack = effective.attack;
+        stat.defense = effective.defense;
+    }
+
+    /// Mints a sword of a class to a player, on the sword contract too once
+    /// the contract is initialized.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `to` - The address of the player.
+    /// * `class` - The sword class.
+    fn mint_sword(env: &Env, to: &Address, class: u32) -> Result<(), Error> {
+        sword_contract::mint(env, to.clone(), class, 1)?;
+        if let Some(sword) = env
+            .storage()
+            .instance()
+            .get::<_, Address>(&DataKey::SwordContract)
+        {
+            sword_contract::SwordContractClient::new(env, &sword).mint_nft(
+                &env.current_contract_address(),
+                to,
+                &class,
+                &1,
+            );
+        }
+        Ok(())
+    }
+
+    /// Melts one of a player's swords of a class, on the sword contract too
+    /// once the contract is initialized.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `from` - The address of the player.
+    /// * `class` - The sword class.
+    fn burn_sword(env: &Env, from: &Address, class: u32) -> Result<(), Error> {
+        sword_contract::burn(env, from.clone(), class)?;
+        if let Some(sword) = env
+            .storage()
+            .instance()
+            .get::<_, Address>(&DataKey::SwordContract)
+        {
+            sword_contract::SwordContractClient::new(env, &sword).melt_blade(
+                &env.current_contract_address(),
+                from,
+                &class,
+            );
+        }
+        Ok(())
+    }
+
+    pub fn melt_blade(env: Env, from: Address, class: u32) -> Result<(), Error> {
+        from.require_auth();
+        let mut player = Self::get_player_stats(env.clone(), from.clone());
+        assert!(player.has_sword, "must own a sword");
+        assert!(
+            player.sword_class == class,
+            "Can only melt the sword you wield"
+        );
+        assert!(!player.in_battle, "Can't melt blade while in battle");
+        Self::burn_sword(&env, &from, class)?;
+
+        player.sword_class = 0;
+        player.has_sword = false;
+        player.durability = 0;
+        Self::apply_sword(&mut player);
+        Self::set_player_stats(env.clone(), from.clone(), player)?;
+        events::sword_melted(&env, from, class);
+        Ok(())
+    }
+
+    /// Fuses two swords of a class into one enhanced sword with twice their
+    /// bonuses. Both swords are burned, and the player wields the enhanced
+    /// sword at full durability.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
+    /// * `class` - The class of the two swords to fuse.
+    pub fn fuse_blades(env: Env, user: Address, class: u32) -> Result<(), Error> {
+        user.require_auth();
+        if Self::get_paused(env.clone()) {
+            return Err(BattleError::ContractPaused.into());
+        }
+        assert!(
+            (1..=ENHANCED_CLASS_OFFSET).contains(&class),
+            "Only forged swords can be fused"
+        );
+        let mut player = Self::get_player_stats(env.clone(), user.clone());
+        assert!(!player.in_battle, "Can't fuse blades while in battle");
+        assert!(
+            !player.has_sword || player.sword_class == class,
+            "Can only fuse the class you wield"
+        );
+        assert!(
+            !player.has_sword || player.durability > 0,
+            "Can't fuse a broken sword"
+        );
+        assert!(
+            SwordContract::balance_of(env.clone(), user.clone(), class) >= 2,
+            "Need two swords of the class to fuse"
+        );
+
+        Self::burn_sword(&env, &user, class)?;
+        Self::burn_sword(&env, &user, class)?;
+        let enhanced = class + ENHANCED_CLASS_OFFSET;
+        Self::mint_sword(&env, &user, enhanced)?;
+
+        player.sword_class = enhanced;
+        player.has_sword = true;
+        player.durability = Self::max_durability(enhanced);
+        Self::apply_sword(&mut player);
+        Self::set_player_stats(env.clone(), user.clone(), player)?;
+        events::sword_fused(&env, user, enhanced);
+        Ok(())
+    }
+
+    /// Restores a player's sword to its class's full durability. A sword that
+    /// has already broken can't be repaired and has to be forged again.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
+    /// * `class` - The class of the player's sword.
+    pub fn repair_blade(env: Env, user: Address, class: u32) -> Result<(), Error> {
+        user.require_auth();
+        let mut player = Self::get_player_stats(env.clone(), user.clone());
+        if !player.has_sword && SwordContract::balance_of(env.clone(), user.clone(), class) > 0 {
+            panic!("Sword is broken, forge a new one");
+        }
+        assert!(
+            player.has_sword && player.sword_class == class,
+            "must own a sword"
+        );
+        assert!(!player.in_battle, "Can't repair blade while in battle");
+
+        player.durability = Self::max_durability(class);
+        Self::set_player_stats(env, user, player)
+    }
+
+    /// Buys potions for a player to use between battles.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
+    /// * `amount` - The number of potions to buy.
+    pub fn buy_potion(env: Env, user: Address, amount: u32) {
+        user.require_auth();
+        assert!(
+            env.storage().instance().has(&DataKey::Player(user.clone())),
+            "Player not registered"
+        );
+        let potions = Self::get_potions(env.clone(), user.clone());
+        env.storage()
+            .instance()
+            .set(&DataKey::Potions(user), &(potions + amount));
+        env.storage()
+            .instance()
+            .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    /// Drinks one of a player's potions, restoring `POTION_HEAL` health up to
+    /// the heal cap. Potions can't be used in battle, or by a player already
+    /// at their maximum health.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
+    pub fn use_potion(env: Env, user: Address) -> Result<(), Error> {
+        user.require_auth();
+        let mut player = Self::get_player_stats(env.clone(), user.clone());
+        assert!(!player.in_battle, "Can't use a potion while in battle");
+        let potions = Self::get_potions(env.clone(), user.clone());
+        if potions == 0 {
+            return Err(BattleError::NoPotions.into());
+        }
+        assert!(
+            player.health < Self::max_health(&player),
+            "Already at full health"
+        );
+
+        env.storage()
+            .instance()
+            .set(&DataKey::Potions(user.clone()), &(potions - 1));
+        Self::apply_heal(&mut player, POTION_HEAL);
+        Self::set_player_stats(env, user, player)
+    }
+
+    /// Gets the number of potions a player holds.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
+    pub fn get_potions(env: Env, user: Address) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::Potions(user))
+            .unwrap_or(0)
+    }
+
+    /// Creates a battle.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    /// * `user` - The address of the player creating the battle.
+    /// * `max_players` - The number of players the battle starts with, up to `MAX_PLAYERS`.
+    pub fn create_battle(
+        env: Env,
+        name: Symbol,
+        user: Address,
+        max_players: u32,
+    ) -> (Result<(), Error>, Result<(), Error>) {
+        user.require_auth();
+        if Self::get_paused(env.clone()) {
+            panic_with_error!(&env, BattleError::ContractPaused);
+        }
+        assert!(
+            (2..=MAX_PLAYERS).contains(&max_players),
+            "Invalid number of players"
+        );
+        if !env.storage().instance().has(&DataKey::Player(user.clone())) {
+            panic_with_error!(&env, BattleError::NotRegistered);
+        }
+        let contract_id = env.current_contract_address();
+        env.storage().instance().set(
+            &DataKey::Battle(name.clone()),
+            &Battle {
+                battle_status: 0,
+                name: name.clone(),
+                players: map![&env, (user.clone(), 1), (contract_id.clone(), 2)],
+                moves: map![&env, (user.clone(), 0), (contract_id.clone(), 0)],
+                turns: 0,
+                winner: OptionalAddress::None,
+                last_move_ts: env.ledger().timestamp(),
+                is_draw: false,
+                result: BattleResult::Ongoing,
+                rounds: Vec::new(&env),
+                round_count: 0,
+                first_mover: OptionalAddress::None,
+                specials: map![&env],
+                invited_at: OptionalLedger::None,
+                max_players,
+                seed_commit: BytesN::from_array(&env, &[0; 32]),
+                revealed_seed: 0,
+            },
+        );
+
+        let mut player = Self::get_player_stats(env.clone(), user.clone());
+        assert!(!player.in_battle, "Player already in battle");
+        player.in_battle = true;
+        player.current_battle = name.clone();
+        let mut battles = Self::get_battles(env.clone());
+        battles.push_back(name.clone());
+
+        let response: (Result<(), Error>, Result<(), Error>) = (
+            Self::set_player_stats(env.clone(), user.clone(), player),
+            Self::set_battles(env.clone(), battles),
+        );
+        events::battle_created(&env, name, user);
+        response
+    }
+
+    /// Creates a two-player battle with a wager. The creator's stake is held
+    /// by the contract, the player who joins has to match it, and the winner
+    /// takes both. If the battle is drawn, cancelled or won by the bot, stakes
+    /// are refunded.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    /// * `user` - The address of the player creating the battle.
+    /// * `amount` - The amount of the wager token each player stakes.
+    pub fn create_battle_with_wager(env: Env, name: Symbol, user: Address, amount: i128) {
+        assert!(amount > 0, "Wager must be positive");
+        let response = Self::create_battle(env.clone(), name.clone(), user.clone(), 2);
+        assert!(response == (Ok(()), Ok(())), "Error creating battle");
+
+        let token = token::Client::new(&env, &Self::get_wager_token(env.clone()));
+        token.transfer(&user, &env.current_contract_address(), &amount);
+        env.storage().instance().set(&DataKey::Wager(name), &amount);
+    }
+
+    /// Creates an auto battle.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    /// * `user` - The address of the player creating the battle.
+    pub fn create_auto_battle(env: Env, name: Symbol, user: Address) -> Result<(), Error> {
+        user.require_auth();
+        if Self::get_paused(env.clone()) {
+            return Err(BattleError::ContractPaused.into());
+        }
+        if !Self::get_bots_enabled(env.clone()) {
+            return Err(BattleError::BotsDisabled.into());
+        }
+        if !env.storage().instance().has(&DataKey::Player(user.clone())) {
+            return Err(BattleError::NotRegistered.into());
+        }
+        let mut player = Self::get_player_stats(env.clone(), user.clone());
+        assert!(!player.in_battle, "Player already in battle");
+        let contract_id = env.current_contract_address();
+        env.storage().instance().set(
+            &DataKey::Battle(name.clone()),
+            &Battle {
+                battle_status: 1,
+                name: name.clone(),
+                players: map![&env, (user.clone(), 1), (contract_id.clone(), 2)],
+                moves: map![&env, (user.clone(), 0), (contract_id.clone(), 0)],
+                turns: 0,
+                winner: OptionalAddress::None,
+                last_move_ts: env.ledger().timestamp(),
+                is_draw: false,
+                result: BattleResult::Ongoing,
+                rounds: Vec::new(&env),
+                round_count: 0,
+                first_mover: OptionalAddress::None,
+                specials: map![&env],
+                invited_at: OptionalLedger::None,
+                max_players: 2,
+                seed_commit: BytesN::from_array(&env, &[0; 32]),
+                revealed_seed: 0,
+            },
+        );
+
+        player.in_battle = true;
+        player.current_battle = name.clone();
+        Self::set_player_stats(env.clone(), user.clone(), player)?;
+
+        let mut battles = Self::get_battles(env.clone());
+        battles.push_back(name.clone());
+        events::battle_created(&env, name.clone(), user);
+        Self::set_battles(env.clone(), battles)
+    }
+
+    /// Cancels a battle that hasn't started, releasing everyone who joined it.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player who created the battle.
+    /// * `name` - The name of the battle.
+    pub fn cancel_battle(env: Env, user: Address, name: Symbol) {
+        user.require_auth();
+        assert!(
+            env.storage().instance().has(&DataKey::Battle(name.clone())),
+            "Battle does not exist"
+        );
+        let battle = Self::get_battle(env.clone(), name.clone());
+        assert!(
+            battle.battle_status == BattleStatus::Pending as u64,
+            "Only a pending battle can be cancelled"
+        );
+        assert_eq!(
+            battle.players.get(user.clone()),
+            Some(1),
+            "Only the creator can cancel the battle"
+        );
+        assert!(
+            !env.storage().instance().has(&DataKey::Invite(name.clone())),
+            "Invites can only be reclaimed once they expire"
+        );
+
+        Self::discard_pending_battle(&env, &battle);
+        events::battle_cancelled(&env, name, user);
+    }
+
+    /// Creates a battle only the invited player can join.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    /// * `challenger` - The address of the player sending the invite.
+    /// * `opponent` - The address of the invited player.
+    pub fn invite_player(env: Env, name: Symbol, challenger: Address, opponent: Address) {
+        if challenger == opponent {
+            panic_with_error!(&env, BattleError::CannotBattleSelf);
+        }
+        if !env
+            .storage()
+            .instance()
+            .has(&DataKey::Player(opponent.clone()))
+        {
+            panic_with_error!(&env, BattleError::NotRegistered);
+        }
+        let response = Self::create_battle(env.clone(), name.clone(), challenger, 2);
+        assert!(response == (Ok(()), Ok(())), "Error creating battle");
+
+        let mut battle = Self::get_battle(env.clone(), name.clone());
+        battle.invited_at = OptionalLedger::Some(env.ledger().sequence());
+        let _ = Self::set_battle(env.clone(), name.clone(), battle);
+        env.storage()
+            .instance()
+            .set(&DataKey::Invite(name), &opponent);
+    }
+
+    /// Gets the player a battle's invite is waiting on, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    pub fn get_invite(env: Env, name: Symbol) -> Option<Address> {
+        env.storage().instance().get(&DataKey::Invite(name))
+    }
+
+    /// Declines an invite, calling off the battle and releasing the
+    /// challenger.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `opponent` - The address of the invited player.
+    /// * `name` - The name of the battle.
+    pub fn decline_invite(env: Env, opponent: Address, name: Symbol) {
+        opponent.require_auth();
+        if Self::get_invite(env.clone(), name.clone()) != Some(opponent.clone()) {
+            panic_with_error!(&env, BattleError::NotInvited);
+        }
+        let battle = Self::get_battle(env.clone(), name.clone());
+        Self::discard_pending_battle(&env, &battle);
+        events::invite_declined(&env, name, opponent);
+    }
+
+    /// Reclaims an invite nobody answered within `INVITE_EXPIRY_LEDGERS`,
+    /// calling off the battle and releasing the challenger.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `challenger` - The address of the player who sent the invite.
+    /// * `name` - The name of the battle.
+    pub fn reclaim_invite(env: Env, challenger: Address, name: Symbol) {
+        challenger.require_auth();
+        if Self::get_invite(env.clone(), name.clone()).is_none() {
+            panic_with_error!(&env, BattleError::NotInvited);
+        }
+        let battle = Self::get_battle(env.clone(), name.clone());
+        assert_eq!(
+            battle.players.get(challenger.clone()),
+            Some(1),
+            "Only the challenger can reclaim the invite"
+        );
+        let invited_at = Option::from(battle.invited_at).unwrap_or(0);
+        assert!(
+            env.ledger().sequence() >= invited_at + INVITE_EXPIRY_LEDGERS,
+            "Invite has not expired"
+        );
+        Self::discard_pending_battle(&env, &battle);
+        events::battle_cancelled(&env, name, challenger);
+    }
+
+    /// A private function to remove a battle that never started, releasing
+    /// everyone who joined it and refunding any wager.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `battle` - The pending battle.
+    fn discard_pending_battle(env: &Env, battle: &Battle) {
+        let name = battle.name.clone();
+        Self::cleanup_battle_participants(env.clone(), battle);
+        Self::settle_wager(env, battle, None);
+        let mut battles = Self::get_battles(env.clone());
+        if let Some(index) = battles.first_index_of(name.clone()) {
+            battles.remove(index);
+        }
+        let _ = Self::set_battles(env.clone(), battles);
+        env.storage()
+            .instance()
+            .remove(&DataKey::Battle(name.clone()));
+        env.storage()
+            .instance()
+            .remove(&DataKey::Spectators(name.clone()));
+        env.storage().instance().remove(&DataKey::Invite(name));
+    }
+
+    /// Joins a battle.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    /// * `user` - The address of the player joining the battle.
+    pub fn join_battle(
+        env: Env,
+        name: Symbol,
+        user: Address,
+    ) -> (Result<(), Error>, Result<(), Error>) {
+        user.require_auth();
+        if Self::get_paused(env.clone()) {
+            panic_with_error!(&env, BattleError::ContractPaused);
+        }
+        if !env.storage().instance().has(&DataKey::Player(user.clone())) {
+            panic_with_error!(&env, BattleError::NotRegistered);
+        }
+        let contract_id = env.current_contract_address();
+        let mut battle = Self::get_battle(env.clone(), name.clone());
+        assert!(battle.battle_status == 0, "Battle already started");
+        if battle.players.contains_key(user.clone()) {
+            panic_with_error!(&env, BattleError::CannotBattleSelf);
+        }
+        match Self::get_invite(env.clone(), name.clone()) {
+            Some(invited) if invited != user => panic_with_error!(&env, BattleError::NotInvited),
+            Some(_) => env
+                .storage()
+                .instance()
+                .remove(&DataKey::Invite(name.clone())),
+            None => {}
+        }
+        let mut player = Self::get_player_stats(env.clone(), user.clone());
+        assert!(!player.in_battle, "Player already in battle");
+
+        // Rebuild the slots in joining order, dropping the contract's placeholder
+        // slot. Map keys are ordered by address, not by slot.
+        let mut players = map![&env];
+        let mut moves = map![&env];
+        let mut slots = 0;
+        for wanted in 1..=battle.players.len() as u64 {
+            if let Some((joined, _)) = battle
+                .players
+                .iter()
+                .find(|(joined, slot)| *slot == wanted && *joined != contract_id)
+            {
+                slots += 1;
+                players.set(joined.clone(), slots);
+                moves.set(joined, 0);
+            }
+        }
+        slots += 1;
+        players.set(user.clone(), slots);
+        moves.set(user.clone(), 0);
+        battle.players = players;
+        battle.moves = moves;
+
+        // The battle starts once it is full
+        if slots == battle.max_players as u64 {
+            battle.battle_status = 1;
+            battle.last_move_ts = env.ledger().timestamp();
+        }
+        player.in_battle = true;
+        player.current_battle = name.clone();
+
+        // The joining player has to match the wager
+        let wager = Self::get_wager(env.clone(), name.clone());
+        if wager > 0 {
+            let token = token::Client::new(&env, &Self::get_wager_token(env.clone()));
+            token.transfer(&user, &contract_id, &wager);
+        }
+
+        // battle = Battle {
+        //     battle_status: 1,
+        //     name: name.clone(),
+        //     players: map![&env, (player_1, 1), (user.clone(), 2)],
+        //     moves: map![&env, (player_1, 0), (user.clone(), 0)],
+        //     winner: contract_id.clone(),
+        // };
+        let response: (Result<(), Error>, Result<(), Error>) = (
+            Self::set_battle(env.clone(), name.clone(), battle.clone()),
+            Self::set_player_stats(env.clone(), user.clone(), player.clone()),
+        );
+
+        assert!(response == (Ok(()), Ok(())), "Error joining battle");
+        events::player_joined(&env, name, user);
+
+        response
+    }
+
+    /// Queues a player for matchmaking. Once another player is waiting, the
+    /// two are paired into a new battle that starts right away.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player looking for a battle.
+    ///
+    /// # Returns
+    ///
+    /// The name of the created battle, or `None` if the player is waiting in the queue.
+    pub fn join_matchmaking(env: Env, user: Address) -> Option<Symbol> {
+        user.require_auth();
+        if Self::get_paused(env.clone()) {
+            panic_with_error!(&env, BattleError::ContractPaused);
+        }
+        if !env.storage().instance().has(&DataKey::Player(user.clone())) {
+            panic_with_error!(&env, BattleError::NotRegistered);
+        }
+        let mut player = Self::get_player_stats(env.clone(), user.clone());
+        assert!(!player.in_battle, "Player already in battle");
+
+        let mut queue: Vec<Address> = env
+            .storage()
+            .instance()
+            .get(&DataKey::Matchmaking)
+            .unwrap_or(Vec::new(&env));
+        if queue.contains(user.clone()) {
+            return None;
+        }
+
+        // Skip anyone who has found a battle some other way since queueing
+        let mut opponent = None;
+        while let Some(waiting) = queue.pop_front() {
+            if !Self::get_player_stats(env.clone(), waiting.clone()).in_battle {
+                opponent = Some(waiting);
+                break;
+            }
+        }
+        let Some(opponent) = opponent else {
+            queue.push_back(user);
+            env.storage().instance().set(&DataKey::Matchmaking, &queue);
+            env.storage()
+                .instance()
+                .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+            return None;
+        };
+        env.storage().instance().set(&DataKey::Matchmaking, &queue);
+
+        let name = Self::next_match_name(&env);
+        let _ = Self::set_battle(
+            env.clone(),
+            name.clone(),
+            Battle {
+                battle_status: BattleStatus::Started as u64,
+                name: name.clone(),
+                players: map![&env, (opponent.clone(), 1), (user.clone(), 2)],
+                moves: map![&env, (opponent.clone(), 0), (user.clone(), 0)],
+                turns: 0,
+                winner: OptionalAddress::None,
+                last_move_ts: env.ledger().timestamp(),
+                is_draw: false,
+                result: BattleResult::Ongoing,
+                rounds: Vec::new(&env),
+                round_count: 0,
+                first_mover: OptionalAddress::None,
+                specials: map![&env],
+                invited_at: OptionalLedger::None,
+                max_players: 2,
+                seed_commit: BytesN::from_array(&env, &[0; 32]),
+                revealed_seed: 0,
+            },
+        );
+        let mut battles = Self::get_battles(env.clone());
+        battles.push_back(name.clone());
+        let _ = Self::set_battles(env.clone(), battles);
+
+        let mut opponent_stats = Self::get_player_stats(env.clone(), opponent.clone());
+        opponent_stats.in_battle = true;
+        opponent_stats.current_battle = name.clone();
+        let _ = Self::set_player_stats(env.clone(), opponent.clone(), opponent_stats);
+        player.in_battle = true;
+        player.current_battle = name.clone();
+        let _ = Self::set_player_stats(env.clone(), user.clone(), player);
+
+        events::battle_created(&env, name.clone(), opponent);
+        events::player_joined(&env, name.clone(), user);
+        Some(name)
+    }
+
+    /// A private function to pick an unused name for a matchmaking battle,
+    /// of the form `Match<n>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    fn next_match_name(env: &Env) -> Symbol {
+        let mut count: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::MatchCount)
+            .unwrap_or(0);
+        loop {
+            count += 1;
+            // Write the count's digits from the end of the buffer backwards
+            let mut buf = *b"Match0000000000";
+            let mut start = buf.len();
+            let mut n = count;
+            loop {
+                start -= 1;
+                buf[start] = b'0' + (n % 10) as u8;
+                n /= 10;
+                if n == 0 {
+                    break;
+                }
+            }
+            buf.copy_within(start.., 5);
+            let len = 5 + buf.len() - start;
+            let name = Symbol::new(env, core::str::from_utf8(&buf[..len]).unwrap());
+            if !env.storage().instance().has(&DataKey::Battle(name.clone())) {
+                env.storage().instance().set(&DataKey::MatchCount, &count);
+                return name;
+            }
+        }
+    }
+
+    /// Starts a battle against the bot, either under a new name or by handing
+    /// the player's own pending battle, which nobody has joined yet, to the bot.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    /// * `user` - The address of the player joining the battle.
+    pub fn challenge_bot(env: Env, user: Address, name: Symbol) -> Result<(), Error> {
+        user.require_auth();
+        if Self::get_paused(env.clone()) {
+            return Err(BattleError::ContractPaused.into());
+        }
+        if !Self::get_bots_enabled(env.clone()) {
+            return Err(BattleError::BotsDisabled.into());
+        }
+        if !env.storage().instance().has(&DataKey::Player(user.clone())) {
+            return Err(BattleError::NotRegistered.into());
+        }
+        let mut battle = Self::get_battle(env.clone(), name.clone());
+        assert!(battle.battle_status == 0, "Battle already started");
+        let mut player = Self::get_player_stats(env.clone(), user.clone());
+        let contract_id = env.current_contract_address();
+
+        if env.storage().instance().has(&DataKey::Battle(name.clone())) {
+            // Only the creator can hand a battle nobody has joined to the bot
+            assert!(
+                battle.players.get(user.clone()) == Some(1)
+                    && battle
+                        .players
+                        .keys()
+                        .iter()
+                        .all(|player| player == user || player == contract_id),
+                "Only the creator of an unjoined battle can challenge the bot"
+            );
+            env.storage()
+                .instance()
+                .remove(&DataKey::Invite(name.clone()));
+        } else {
+            assert!(!player.in_battle, "Player already in battle");
+            let mut battles = Self::get_battles(env.clone());
+            battles.push_back(name.clone());
+            Self::set_battles(env.clone(), battles)?;
+        }
+
+        battle.players = map![&env, (user.clone(), 1), (contract_id.clone(), 2)];
+        battle.battle_status = 1;
+        battle.last_move_ts = env.ledger().timestamp();
+        player.in_battle = true;
+        player.current_battle = name.clone();
+
+        Self::set_player_stats(env.clone(), user.clone(), player)?;
+        events::player_joined(&env, name.clone(), user);
+        Self::set_battle(env.clone(), name.clone(), battle)
+    }
+
+    /// Commits the battle's creator to a secret seed before any move is made.
+    /// Once revealed, the seed is mixed with the ledger into the battle's
+    /// randomness, so neither the player nor the validator controls it alone.
+    /// Rounds aren't resolved until the seed is revealed, and a creator who
+    /// doesn't reveal it in time forfeits.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    /// * `user` - The address of the battle's creator.
+    /// * `commit` - The SHA-256 hash of the seed's big-endian bytes.
+    pub fn commit_battle_seed(env: Env, name: Symbol, user: Address, commit: BytesN<32>) {
+        user.require_auth();
+        let mut battle = Self::get_battle(env.clone(), name.clone());
+        assert!(
+            battle.players.get(user) == Some(1),
+            "Only the battle's creator can commit a seed"
+        );
+        assert!(
+            battle.turns == 0 && battle.rounds.is_empty(),
+            "Moves have already been made"
+        );
+        assert!(
+            battle.seed_commit == BytesN::from_array(&env, &[0; 32]),
+            "Seed already committed"
+        );
+        battle.seed_commit = commit;
+        let _ = Self::set_battle(env, name, battle);
+    }
+
+    /// Reveals the seed committed with `commit_battle_seed`, resolving the
+    /// round if everyone has already moved.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    /// * `user` - The address of the battle's creator.
+    /// * `seed` - The committed seed.
+    pub fn reveal_battle_seed(
+        env: Env,
+        name: Symbol,
+        user: Address,
+        seed: u64,
+    ) -> Result<(), Error> {
+        user.require_auth();
+        let mut battle = Self::get_battle(env.clone(), name.clone());
+        assert!(
+            battle.players.get(user.clone()) == Some(1),
+            "Only the battle's creator can reveal the seed"
+        );
+        assert!(
+            battle.battle_status != BattleStatus::Ended as u64,
+            "Battle already ended"
+        );
+        if battle.seed_commit == BytesN::from_array(&env, &[0; 32])
+            || Self::seed_hash(&env, seed) != battle.seed_commit
+        {
+            return Err(BattleError::SeedMismatch.into());
+        }
+        battle.revealed_seed = seed;
+        let everyone_moved = battle.battle_status == BattleStatus::Started as u64
+            && battle.moves.values().iter().all(|choice| choice != 0);
+        Self::set_battle(env.clone(), name.clone(), battle)?;
+        if everyone_moved {
+            Self::await_battle_results(env, name, user);
+        }
+        Ok(())
+    }
+
+    /// The hash a seed is committed to.
+    fn seed_hash(env: &Env, seed: u64) -> BytesN<32> {
+        env.crypto()
+            .sha256(&Bytes::from_array(env, &seed.to_be_bytes()))
+    }
+
+    /// Whether a battle's creator committed a seed they haven't revealed yet.
+    fn seed_unrevealed(env: &Env, battle: &Battle) -> bool {
+        battle.seed_commit != BytesN::from_array(env, &[0; 32])
+            && Self::seed_hash(env, battle.revealed_seed) != battle.seed_commit
+    }
+
+    /// Advances bot battles that are waiting on the bot's move.
+    ///
+    /// For each started battle against the contract where the player has already
+    /// moved, a bot move is generated and the round is resolved. At most
+    /// `MAX_TICK_BATTLES` battles are advanced per call to bound the work done.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `max` - The maximum number of battles to advance.
+    ///
+    /// # Returns
+    ///
+    /// The number of battles advanced.
+    pub fn tick(env: Env, max: u32) -> u32 {
+        if !Self::get_bots_enabled(env.clone()) {
+            panic_with_error!(&env, BattleError::BotsDisabled);
+        }
+        let max = max.min(MAX_TICK_BATTLES);
+        let contract_id = env.current_contract_address();
+        let mut advanced = 0;
+
+        let battles = Self::get_battles(env.clone());
+        for (index, name) in battles.iter().enumerate() {
+            if advanced >= max {
+                break;
+            }
+            let mut battle = Self::get_battle(env.clone(), name.clone());
+            if battle.battle_status != BattleStatus::Started as u64
+                || !battle.players.contains_key(contract_id.clone())
+                || battle.moves.get(contract_id.clone()).unwrap_or(0) != 0
+                || battle.turns == 0
+            {
+                continue;
+            }
+
+            let seed = index as u64 ^ battle.revealed_seed;
+            battle
+                .moves
+                .set(contract_id.clone(), Self::bot_move(&env, seed));
+            battle.turns += 1;
+            let _ = Self::set_battle(env.clone(), name.clone(), battle);
+            Self::await_battle_results(env.clone(), name, contract_id.clone());
+            advanced += 1;
+        }
+        advanced
+    }
+
+    /// Picks the bot's move for the current round at random.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `seed` - A per-battle seed, so battles advanced together don't all move alike.
+    fn bot_move(env: &Env, seed: u64) -> u64 {
+        let moves = Self::all_moves(env);
+        let index = rng::next_u64(env, seed, 0) % moves.len() as u64;
+        moves.get_unchecked(index as u32)
+    }
+
+    /// Claims the win of a battle whose opponent has stopped making moves.
+    ///
+    /// The caller must have made their move this round, and the opponent must
+    /// have failed to respond for longer than the move timeout. In a
+    /// free-for-all, the idle players are knocked out and the others play on.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player claiming the win.
+    /// * `name` - The name of the battle.
+    pub fn claim_timeout_win(env: Env, user: Address, name: Symbol) {
+        user.require_auth();
+        let battle = Self::get_battle(env.clone(), name.clone());
+        assert!(
+            battle.battle_status == BattleStatus::Started as u64,
+            "Battle not in progress"
+        );
+        assert!(
+            battle.players.contains_key(user.clone()),
+            "You are not in this battle"
+        );
+        assert!(
+            battle.moves.get(user.clone()).unwrap_or(0) != 0,
+            "You have not made your move"
+        );
+
+        let mut idle = Vec::new(&env);
+        for player in battle.players.keys().iter() {
+            if battle.moves.get(player.clone()).unwrap_or(0) == 0 {
+                idle.push_back(player);
+            }
+        }
+        // A creator who holds up the round by not revealing their seed is
+        // idle too
+        if idle.is_empty() && Self::seed_unrevealed(&env, &battle) {
+            if let Some(creator) = Self::player_in_slot(&battle, 1) {
+                if creator != user {
+                    idle.push_back(creator);
+                }
+            }
+        }
+        assert!(!idle.is_empty(), "Opponent has already made their move");
+        assert!(
+            env.ledger().timestamp() > battle.last_move_ts + Self::get_move_timeout(env.clone()),
+            "Opponent still has time to move"
+        );
+
+        // In a free-for-all only the idle players are out, and the rest play on
+        if battle.players.len() > 2 {
+            return Self::leave_free_for_all(env, name, idle);
+        }
+        Self::end_battle(env.clone(), name.clone(), user);
+    }
+
+    /// Previews who would be awarded the win if a timeout were claimed now.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    ///
+    /// # Returns
+    ///
+    /// The player who moved while their opponent didn't, once the opponent has
+    /// been idle for longer than the move timeout, or `None` otherwise.
+    pub fn timeout_winner(env: Env, name: Symbol) -> Option<Address> {
+        let battle = Self::get_battle(env.clone(), name);
+        if battle.battle_status != BattleStatus::Started as u64
+            || env.ledger().timestamp() <= battle.last_move_ts + Self::get_move_timeout(env.clone())
+        {
+            return None;
+        }
+
+        let mut moved = battle
+            .players
+            .keys()
+            .iter()
+            .filter(|player| battle.moves.get(player.clone()).unwrap_or(0) != 0);
+        if battle.players.len() == 2 && Self::seed_unrevealed(&env, &battle) {
+            return moved.find(|player| battle.players.get(player.clone()) != Some(1));
+        }
+        match (moved.next(), moved.next()) {
+            (Some(player), None) => Some(player),
+            _ => None,
+        }
+    }
+
+    /// Forfeits a battle, declaring the opponent the winner. In a free-for-all,
+    /// only the forfeiting player is out and the others play on.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player forfeiting the battle.
+    /// * `name` - The name of the battle.
+    pub fn forfeit_battle(env: Env, user: Address, name: Symbol) {
+        user.require_auth();
+        let battle = Self::get_battle(env.clone(), name.clone());
+        assert!(
+            battle.battle_status == BattleStatus::Started as u64,
+            "Only a started battle can be forfeited"
+        );
+        assert!(
+            battle.players.contains_key(user.clone()),
+            "You are not in this battle"
+        );
+
+        // A free-for-all goes on without the forfeiting player
+        if battle.players.len() > 2 {
+            return Self::leave_free_for_all(env.clone(), name, vec![&env, user]);
+        }
+
+        let opponent = battle
+            .players
+            .keys()
+            .iter()
+            .find(|player| *player != user)
+            .unwrap_or(env.current_contract_address());
+
+        // end_battle releases both participants, including the forfeiting player
+        Self::end_battle(env.clone(), name.clone(), opponent);
+    }
+
+    /// Starts a finished two-player battle over with the same players, who
+    /// return at full health with no moves made. Every player must still be
+    /// registered.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of a player of the finished battle.
+    /// * `name` - The name of the battle.
+    pub fn rematch(env: Env, user: Address, name: Symbol) {
+        user.require_auth();
+        if Self::get_paused(env.clone()) {
+            panic_with_error!(&env, BattleError::ContractPaused);
+        }
+        let mut battle = Self::get_battle(env.clone(), name.clone());
+        assert!(
+            battle.battle_status == BattleStatus::Ended as u64,
+            "Only an ended battle can be rematched"
+        );
+        assert!(
+            battle.players.contains_key(user.clone()),
+            "You are not in this battle"
+        );
+        assert!(battle.players.len() == 2, "Only two players can rematch");
+
+        let contract_id = env.current_contract_address();
+        for player in battle.players.keys().iter() {
+            if player == contract_id {
+                if !Self::get_bots_enabled(env.clone()) {
+                    panic_with_error!(&env, BattleError::BotsDisabled);
+                }
+                continue;
+            }
+            if !env
+                .storage()
+                .instance()
+                .has(&DataKey::Player(player.clone()))
+            {
+                panic_with_error!(&env, BattleError::NotRegistered);
+            }
+            let mut stats = Self::get_player_stats(env.clone(), player.clone());
+            assert!(!stats.in_battle, "Player already in battle");
+            stats.in_battle = true;
+            stats.current_battle = name.clone();
+            stats.health = Self::effective_stats(&stats).health;
+            let _ = Self::set_player_stats(env.clone(), player, stats);
+        }
+
+        battle.battle_status = BattleStatus::Started as u64;
+        for player in battle.players.keys().iter() {
+            battle.moves.set(player, 0);
+        }
+        battle.turns = 0;
+        battle.winner = OptionalAddress::None;
+        battle.last_move_ts = env.ledger().timestamp();
+        battle.is_draw = false;
+        battle.result = BattleResult::Ongoing;
+        battle.rounds = Vec::new(&env);
+        battle.round_count = 0;
+        battle.first_mover = OptionalAddress::None;
+        battle.specials = map![&env];
+        battle.seed_commit = BytesN::from_array(&env, &[0; 32]);
+        battle.revealed_seed = 0;
+        let _ = Self::set_battle(env.clone(), name.clone(), battle);
+
+        // Move the battle from the archive back into the battle list
+        let mut archive = Self::get_battle_archive(env.clone());
+        if let Some(index) = archive.first_index_of(name.clone()) {
+            archive.remove(index);
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::BattleArchive, &archive);
+        let mut battles = Self::get_battles(env.clone());
+        battles.push_back(name.clone());
+        let _ = Self::set_battles(env.clone(), battles);
+        events::battle_rematched(&env, name, user);
+    }
+
+    /// Resolves the current round of a battle. Callable by anyone.
+    ///
+    /// The round is resolved once both participants have moved. The bot's move
+    /// is generated if it hasn't moved yet, and once the move timeout has
+    /// passed since the last move, a player who hasn't moved defends.
+    /// A battle with a committed seed waits for it to be revealed.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    pub fn resolve_battle(env: Env, name: Symbol) {
+        let mut battle = Self::get_battle(env.clone(), name.clone());
+        assert!(
+            battle.battle_status == BattleStatus::Started as u64,
+            "Battle not in progress"
+        );
+        assert!(
+            !Self::seed_unrevealed(&env, &battle),
+            "Battle seed not revealed yet"
+        );
+
+        let contract_id = env.current_contract_address();
+        let timed_out =
+            env.ledger().timestamp() > battle.last_move_ts + Self::get_move_timeout(env.clone());
+        for player in battle.players.keys().iter() {
+            if battle.moves.get(player.clone()).unwrap_or(0) != 0 {
+                continue;
+            }
+            let choice = if player == contract_id {
+                if !Self::get_bots_enabled(env.clone()) {
+                    panic_with_error!(&env, BattleError::BotsDisabled);
+                }
+                let seed = Self::get_battles(env.clone())
+                    .first_index_of(name.clone())
+                    .unwrap_or(0);
+                Self::bot_move(&env, seed as u64 ^ battle.revealed_seed)
+            } else {
+                assert!(timed_out, "Players have not made their moves yet");
+                2
+            };
+            battle.moves.set(player, choice);
+            battle.turns += 1;
+        }
+
+        let _ = Self::set_battle(env.clone(), name.clone(), battle);
+        Self::await_battle_results(env, name, contract_id);
+    }
+
+    /// Gets the winner of a battle.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    ///
+    /// # Returns
+    ///
+    /// The winner once the battle has ended, or `None` while it is still
+    /// pending or being fought, or if it ended in a draw.
+    pub fn get_winner(env: Env, name: Symbol) -> Option<Address> {
+        let battle = Self::get_battle(env, name);
+        if battle.battle_status == BattleStatus::Ended as u64 {
+            battle.winner.into()
+        } else {
+            None
+        }
+    }
+
+    /// Gets the round-by-round history of a battle.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    pub fn get_battle_history(env: Env, name: Symbol) -> Vec<Round> {
+        Self::get_battle(env, name).rounds
+    }
 
-    pub fn forge_blade(env: Env, to: Address, class: u32) -> Result<(), Error> {
-        to.require_auth();
-        let player_info = Self::get_player_stats(env.clone(), to.clone());
-        assert!(!player_info.has_sword, "must not own a sword");
-        assert!(!player_info.in_battle, "Can't forge blade while in battle");
-        assert!(!class > 2, "Invalid sword class");
+    /// Watches a battle that hasn't ended yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the spectator.
+    /// * `name` - The name of the battle.
+    pub fn watch_battle(env: Env, user: Address, name: Symbol) {
+        user.require_auth();
+        let battle = Self::get_battle(env.clone(), name.clone());
+        assert!(
+            env.storage().instance().has(&DataKey::Battle(name.clone())),
+            "Battle does not exist"
+        );
+        assert!(
+            battle.battle_status != BattleStatus::Ended as u64,
+            "Battle already ended"
+        );
 
-        let mut player = Self::get_player_stats(env.clone(), to.clone());
-        match class {
-            1 => {
-                player.health += 8;
-                player.attack += 4;
-                player.defense += 3;
+        let mut spectators = Self::get_spectators(env.clone(), name.clone());
+        if spectators.contains(user.clone()) {
+            return;
+        }
+        assert!(
+            spectators.len() < Self::get_max_spectators(env.clone()),
+            "Battle has too many spectators"
+        );
+        spectators.push_back(user);
+        env.storage()
+            .instance()
+            .set(&DataKey::Spectators(name), &spectators);
+        env.storage()
+            .instance()
+            .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    /// Gets the spectators of a battle.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    pub fn get_spectators(env: Env, name: Symbol) -> Vec<Address> {
+        env.storage()
+            .instance()
+            .get(&DataKey::Spectators(name))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Gets the damage each player dealt and took in a battle's last resolved
+    /// round, as `(player, dealt, taken)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    pub fn last_round_damage(env: Env, name: Symbol) -> Vec<(Address, u32, u32)> {
+        let mut log = Vec::new(&env);
+        if let Some(round) = Self::get_battle(env, name).rounds.last() {
+            for (player, dealt) in round.dealt.iter() {
+                let taken = round.taken.get(player.clone()).unwrap_or(0);
+                log.push_back((player, dealt, taken));
+            }
+        }
+        log
+    }
+
+    /// Gets the battle a player is currently in.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
+    pub fn get_player_battle(env: Env, user: Address) -> Option<Symbol> {
+        let stat = Self::get_player_stats(env.clone(), user);
+        if stat.in_battle {
+            Some(stat.current_battle)
+        } else {
+            None
+        }
+    }
+
+    /// Gets the health a player has lost across all battles.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
+    pub fn get_damage_taken(env: Env, user: Address) -> u64 {
+        Self::get_player_stats(env, user).total_damage_taken
+    }
+
+    /// Previews a battle, comparing both players' statistics and odds.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    pub fn match_preview(env: Env, name: Symbol) -> MatchPreview {
+        let battle = Self::get_battle(env.clone(), name);
+        let players = Self::players_by_slot(&env, &battle);
+        let player_a = players.get(0).expect("Battle is missing a player");
+        let player_b = players.get(1).expect("Battle is missing a player");
+        let a_effective = Self::get_player_stats(env.clone(), player_a.clone());
+        let b_effective = Self::get_player_stats(env.clone(), player_b.clone());
+        let a_win_pct = Self::win_pct(&a_effective, &b_effective);
+
+        MatchPreview {
+            player_a,
+            player_b,
+            a_effective,
+            b_effective,
+            a_win_pct,
+        }
+    }
+
+    /// Gets a two-player battle with its players and moves as plain fields.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    pub fn get_battle_flat(env: Env, name: Symbol) -> FlatBattle {
+        let battle = Self::get_battle(env.clone(), name);
+        let player_a = Self::player_in_slot(&battle, 1);
+        let player_b = Self::player_in_slot(&battle, 2);
+        let move_of = |player: &Option<Address>| {
+            player
+                .clone()
+                .and_then(|player| battle.moves.get(player))
+                .unwrap_or(0)
+        };
+
+        FlatBattle {
+            move_a: move_of(&player_a),
+            move_b: move_of(&player_b),
+            name: battle.name,
+            status: battle.battle_status,
+            player_a: player_a.into(),
+            player_b: player_b.into(),
+            winner: battle.winner,
+            round: battle.round_count,
+        }
+    }
+
+    /// The player in a battle's slot, or `None` if the slot is empty.
+    fn player_in_slot(battle: &Battle, slot: u64) -> Option<Address> {
+        battle
+            .players
+            .iter()
+            .find(|(_, s)| *s == slot)
+            .map(|(player, _)| player)
+    }
+
+    /// The players still in a battle, in the order they joined it.
+    fn players_by_slot(env: &Env, battle: &Battle) -> Vec<Address> {
+        let mut players = Vec::new(env);
+        for slot in 1..=MAX_PLAYERS as u64 {
+            if let Some(player) = Self::player_in_slot(battle, slot) {
+                players.push_back(player);
             }
-            2 => {
-                player.health -= 3;
-                player.attack += 16;
-                player.defense += 2;
+        }
+        players
+    }
+
+    /// Returns the names of the battles with the given status.
+    fn battles_with_status(env: &Env, status: BattleStatus) -> Vec<Symbol> {
+        let mut names = Vec::new(env);
+        for name in Self::get_battles(env.clone()).iter() {
+            let battle: Battle = env
+                .storage()
+                .instance()
+                .get(&DataKey::Battle(name.clone()))
+                .unwrap();
+            if battle.battle_status == status as u64 {
+                names.push_back(name);
             }
-            3 => {
-                player.health += 7;
-                player.attack += 11;
-                player.defense -= 3;
+        }
+        names
+    }
+
+    /// Estimates the chance, in percent, that `a` beats `b` when both keep
+    /// attacking, based on how many rounds each needs to knock the other out.
+    /// The estimate for `b` against `a` is always the complement.
+    fn win_pct(a: &PlayerStat, b: &PlayerStat) -> u32 {
+        let a_rounds = b.health.div_ceil(a.attack.max(1)) as i64;
+        let b_rounds = a.health.div_ceil(b.attack.max(1)) as i64;
+        (50 + (b_rounds - a_rounds) * 50 / (a_rounds + b_rounds).max(1)) as u32
+    }
+
+    /// Sets a battle.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    /// * `battle` - The Battle struct containing the battle information.
+    fn set_battle(env: Env, name: Symbol, battle: Battle) -> Result<(), Error> {
+        env.storage()
+            .instance()
+            .set(&DataKey::Battle(name.clone()), &battle);
+
+        env.storage()
+            .instance()
+            .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        Ok(())
+    }
+
+    /// Gets a battle as a given player or spectator may see it: every move
+    /// but the viewer's own is hidden (0) until the round resolves. Resolved
+    /// rounds keep all their moves.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    /// * `viewer` - The address of the player or spectator viewing the battle.
+    ///
+    /// # Returns
+    ///
+    /// A Battle struct containing the battle information visible to the viewer.
+    pub fn get_battle_public(env: Env, name: Symbol, viewer: Address) -> Battle {
+        let mut battle = Self::get_battle(env, name);
+        for player in battle.moves.keys().iter() {
+            if player != viewer {
+                battle.moves.set(player, 0);
             }
-            _ => {
-                player.health += 8;
-                player.attack += 4;
-                player.defense += 3;
+        }
+        battle
+    }
+
+    /// Gets a battle.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    ///
+    /// # Returns
+    ///
+    /// A Battle struct containing the battle information.
+    pub fn get_battle(env: Env, name: Symbol) -> Battle {
+        let contract_id = env.current_contract_address();
+        env.storage()
+            .instance()
+            .get(&DataKey::Battle(name.clone()))
+            .unwrap_or(Battle {
+                battle_status: 0,
+                name: name.clone(),
+                players: map![&env, (contract_id.clone(), 1), (contract_id.clone(), 2)],
+                moves: map![&env, (contract_id.clone(), 0), (contract_id.clone(), 0)],
+                turns: 0,
+                winner: OptionalAddress::None,
+                last_move_ts: 0,
+                is_draw: false,
+                result: BattleResult::Ongoing,
+                rounds: Vec::new(&env),
+                round_count: 0,
+                first_mover: OptionalAddress::None,
+                specials: map![&env],
+                invited_at: OptionalLedger::None,
+                max_players: 2,
+                seed_commit: BytesN::from_array(&env, &[0; 32]),
+                revealed_seed: 0,
+            })
+    }
+
+    /// Gets the players of a battle in slot order, starting with its creator.
+    /// The contract's placeholder in a pending battle and its slot in a bot
+    /// battle are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    pub fn get_battle_players(env: Env, name: Symbol) -> Vec<Address> {
+        let contract_id = env.current_contract_address();
+        let battle = Self::get_battle(env.clone(), name);
+        let mut players = Vec::new(&env);
+        for player in Self::players_by_slot(&env, &battle).iter() {
+            if player != contract_id {
+                players.push_back(player);
             }
         }
-
-        player.sword_class = class;
-        player.has_sword = true;
-        let _ = SwordContract::mint_nft(env.clone(), to.clone(), class.clone(), 1);
-        Self::set_player_stats(env.clone(), to.clone(), player)
+        players
+    }
+
+    /// Sets the list of battles.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `battles` - The Vec<Symbol> containing the names of the battles.
+    fn set_battles(env: Env, battles: Vec<Symbol>) -> Result<(), Error> {
+        env.storage().instance().set(&DataKey::Battles, &battles);
+        env.storage()
+            .instance()
+            .set(&DataKey::BattleCount, &battles.len());
+        env.storage()
+            .instance()
+            .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        Ok(())
+    }
+
+    /// Gets the list of battles.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    ///
+    /// # Returns
+    ///
+    /// A Vec<Symbol> containing the names of the battles.
+    pub fn get_battles(env: Env) -> Vec<Symbol> {
+        env.storage()
+            .instance()
+            .get(&DataKey::Battles)
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Gets the number of battles in the battle list without reading it.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    pub fn battle_count(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::BattleCount)
+            .unwrap_or(0)
+    }
+
+    /// Gets the battles that are currently being fought.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    ///
+    /// # Returns
+    ///
+    /// A Vec<Symbol> containing the names of the started battles.
+    pub fn get_active_battles(env: Env) -> Vec<Symbol> {
+        Self::battles_with_status(&env, BattleStatus::Started)
+    }
+
+    /// Gets the battles still waiting for opponents to join.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    ///
+    /// # Returns
+    ///
+    /// A Vec<Symbol> containing the names of the pending battles.
+    pub fn get_open_battles(env: Env) -> Vec<Symbol> {
+        Self::battles_with_status(&env, BattleStatus::Pending)
+    }
+
+    /// Gets the battles that have ended, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    ///
+    /// # Returns
+    ///
+    /// A Vec<Symbol> containing the names of the ended battles.
+    pub fn get_battle_archive(env: Env) -> Vec<Symbol> {
+        env.storage()
+            .instance()
+            .get(&DataKey::BattleArchive)
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Gets a page of the battle log, the ended two-player battles in the
+    /// order they ended.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `start` - The index of the first entry on the page.
+    /// * `count` - The number of entries on the page, up to `MAX_PAGE_SIZE`.
+    ///
+    /// # Returns
+    ///
+    /// A Vec of BattleLogEntry, empty if `start` is past the last entry.
+    pub fn get_battle_log(env: Env, start: u32, count: u32) -> Vec<BattleLogEntry> {
+        let log: Vec<BattleLogEntry> = env
+            .storage()
+            .instance()
+            .get(&DataKey::BattleLog)
+            .unwrap_or(Vec::new(&env));
+        let start = start.min(log.len());
+        let end = start
+            .saturating_add(count.min(MAX_PAGE_SIZE))
+            .min(log.len());
+        log.slice(start..end)
+    }
+
+    /// Gets how many players still have to move in the current round of a
+    /// battle. A battle that isn't in progress has no moves remaining.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    pub fn moves_remaining(env: Env, name: Symbol) -> u64 {
+        let battle = Self::get_battle(env, name);
+        if battle.battle_status != BattleStatus::Started as u64 {
+            return 0;
+        }
+        battle.moves.values().iter().filter(|m| *m == 0).count() as u64
+    }
+
+    /// Gets the moves a player may currently submit in a battle: a light
+    /// attack (1), defend (2) and a heavy attack (3) while they still owe a
+    /// move this round, nothing otherwise. This only reports whether the
+    /// player owes a move: players knocked out of a free-for-all are no longer
+    /// in the battle and get nothing, and whether their special is still
+    /// available isn't covered, since `use_special` is not a move.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    /// * `user` - The address of the player.
+    pub fn valid_moves(env: Env, name: Symbol, user: Address) -> Vec<u64> {
+        let battle = Self::get_battle(env.clone(), name);
+        let owes_move = battle.battle_status == BattleStatus::Started as u64
+            && user != env.current_contract_address()
+            && battle.players.contains_key(user.clone())
+            && battle.moves.get(user).unwrap_or(0) == 0;
+        if owes_move {
+            Self::all_moves(&env)
+        } else {
+            Vec::new(&env)
+        }
     }
 
-    pub fn melt_blade(env: Env, from: Address, class: u32) -> Result<(), Error> {
-        from.require_auth();
-        let mut player = Self::get_player_stats(env.clone(), from.clone());
-        assert!(player.has_sword, "must own a sword");
-        assert!(!player.in_battle, "Can't melt blade while in battle");
-        let _ = SwordContract::melt_blade(env.clone(), from.clone(), class.clone());
-
-        player.health = 100;
-        player.attack = 10;
-        player.defense = 10;
-        player.sword_class = 0;
-        player.has_sword = false;
-        Self::set_player_stats(env.clone(), from.clone(), player)
+    /// Every move a player can make: a light attack (1), defend (2) and a
+    /// heavy attack (3).
+    fn all_moves(env: &Env) -> Vec<u64> {
+        vec![env, 1, 2, 3]
     }
 
-    /// Creates a battle.
+    /// Handles player's attack or defend choice in a battle. Moves beat each
+    /// other like rock-paper-scissors: a light attack interrupts a heavy one,
+    /// a heavy attack breaks through a defense, and a defense blocks a light
+    /// attack.
     ///
     /// # Arguments
     ///
     /// * `env` - The contract execution environment.
-    /// * `name` - The name of the battle.
-    /// * `user` - The address of the player creating the battle.
-    pub fn create_battle(
+    /// * `user` - The address of the player making the choice.
+    /// * `choice` - The choice made by the player: 1 for a light attack, 2 to
+    ///   defend or 3 for a heavy attack.
+    /// * `battle_name` - The name of the battle in which the choice is made.
+    ///
+    /// # Returns
+    ///
+    /// The result of the battle, which is `Ongoing` unless the move ended it.
+    pub fn attack_or_defend_choice(
         env: Env,
-        name: Symbol,
         user: Address,
-    ) -> (Result<(), Error>, Result<(), Error>) {
+        choice: u64,
+        battle_name: Symbol,
+    ) -> Result<BattleResult, Error> {
         user.require_auth();
-        // Todo user must be registered
-        let contract_id = env.current_contract_address();
-        env.storage().instance().set(
-            &DataKey::Battle(name.clone()),
-            &Battle {
-                battle_status: 0,
-                name: name.clone(),
-                players: map![&env, (user.clone(), 1), (contract_id.clone(), 2)],
-                moves: map![&env, (user.clone(), 0), (contract_id.clone(), 0)],
-                turns: 0,
-                winner: contract_id.clone(),
-            },
-        );
+        if user == env.current_contract_address() {
+            return Err(BattleError::BotMoveNotAllowed.into());
+        }
+        if !Self::all_moves(&env).contains(choice) {
+            return Err(BattleError::InvalidChoice.into());
+        }
+        let battle = Self::get_battle(env.clone(), battle_name.clone());
 
-        let mut player = Self::get_player_stats(env.clone(), user.clone());
-        assert!(!player.in_battle, "Player already in battle");
-        player.in_battle = true;
-        let mut battles = Self::get_battles(env.clone());
-        battles.push_back(name.clone());
+        // Require that battle has started
+        if battle.battle_status != 1 {
+            return Err(BattleError::BattleNotStarted.into());
+        }
+        // Require that player is in the battle
+        if !battle.players.contains_key(user.clone()) {
+            return Err(BattleError::NotInBattle.into());
+        }
+        // Require that player has not made a move yet
+        if battle.moves.get(user.clone()).unwrap_or(0) != 0 {
+            return Err(BattleError::AlreadyMoved.into());
+        }
 
-        let response: (Result<(), Error>, Result<(), Error>) = (
-            Self::set_player_stats(env.clone(), user.clone(), player),
-            Self::set_battles(env.clone(), battles),
-        );
-        response
+        let mut battle = Self::get_battle(env.clone(), battle_name.clone());
+        battle.turns += 1;
+        battle.moves.set(user.clone(), choice);
+        battle.last_move_ts = env.ledger().timestamp();
+        if battle.first_mover == OptionalAddress::None {
+            battle.first_mover = OptionalAddress::Some(user.clone());
+        }
+
+        let _ = Self::set_battle(env.clone(), battle_name.clone(), battle.clone());
+        Self::record_player_move(&env, user.clone(), choice);
+        events::move_registered(&env, battle_name.clone(), user.clone());
+
+        // Resolve the round once every participant, whoever they are, has
+        // moved, unless it still waits for the creator's seed
+        let everyone_moved = battle
+            .players
+            .keys()
+            .iter()
+            .all(|player| battle.moves.get(player).unwrap_or(0) != 0);
+        if everyone_moved && !Self::seed_unrevealed(&env, &battle) {
+            Self::resolve_battle(env.clone(), battle_name.clone());
+        }
+        Ok(Self::get_battle(env, battle_name).result)
     }
 
-    /// Creates an auto battle.
+    /// Uses the special of a player's sword, once per battle. It takes effect
+    /// when the current round resolves: a Longsword's Parry negates the hit
+    /// the player takes, a Sabre's Flurry doubles the player's hit, and a
+    /// Claymore's Cleave ignores the opponent's defense. Enhanced swords have
+    /// the special of the class they were fused from.
     ///
     /// # Arguments
     ///
     /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
     /// * `name` - The name of the battle.
-    /// * `user` - The address of the player creating the battle.
-    pub fn create_auto_battle(env: Env, name: Symbol, user: Address) -> Result<(), Error> {
+    pub fn use_special(env: Env, user: Address, name: Symbol) -> Result<(), Error> {
         user.require_auth();
-        let contract_id = env.current_contract_address();
-        env.storage().instance().set(
-            &DataKey::Battle(name.clone()),
-            &Battle {
-                battle_status: 1,
-                name: name.clone(),
-                players: map![&env, (user.clone(), 1), (contract_id.clone(), 2)],
-                moves: map![&env, (user.clone(), 0), (contract_id.clone(), 0)],
-                turns: 0,
-                winner: contract_id.clone(),
-            },
+        let mut battle = Self::get_battle(env.clone(), name.clone());
+        if battle.battle_status != BattleStatus::Started as u64 {
+            return Err(BattleError::BattleNotStarted.into());
+        }
+        if !battle.players.contains_key(user.clone()) {
+            return Err(BattleError::NotInBattle.into());
+        }
+        assert!(
+            battle.players.len() == 2,
+            "Specials are only for two-player battles"
         );
+        if battle.specials.contains_key(user.clone()) {
+            return Err(BattleError::SpecialUsed.into());
+        }
+        let player = Self::get_player_stats(env.clone(), user.clone());
+        assert!(player.has_sword, "must own a sword");
 
-        let mut battles = Self::get_battles(env.clone());
-        battles.push_back(name.clone());
-        Self::set_battles(env.clone(), battles)
+        battle.specials.set(user, true);
+        Self::set_battle(env, name, battle)
     }
 
-    /// Joins a battle.
+    /// Gets the moves a player has submitted across all battles, oldest
+    /// first. Only the last `MAX_MOVE_HISTORY` moves are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
+    pub fn get_player_moves(env: Env, user: Address) -> Vec<u64> {
+        env.storage()
+            .instance()
+            .get(&DataKey::PlayerMoves(user))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// A private function to append a move to a player's move history,
+    /// dropping the oldest once it is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `user` - The address of the player.
+    /// * `choice` - The move the player submitted.
+    fn record_player_move(env: &Env, user: Address, choice: u64) {
+        let mut moves = Self::get_player_moves(env.clone(), user.clone());
+        if moves.len() >= MAX_MOVE_HISTORY {
+            moves.pop_front();
+        }
+        moves.push_back(choice);
+        env.storage()
+            .instance()
+            .set(&DataKey::PlayerMoves(user), &moves);
+    }
+
+    /// A private function to await battle results.
     ///
     /// # Arguments
     ///
     /// * `env` - The contract execution environment.
     /// * `name` - The name of the battle.
-    /// * `user` - The address of the player joining the battle.
-    pub fn join_battle(
-        env: Env,
-        name: Symbol,
-        user: Address,
-    ) -> (Result<(), Error>, Result<(), Error>) {
-        user.require_auth();
+    /// * `user` - The address of the user.
+    fn await_battle_results(env: Env, name: Symbol, _user: Address) {
+        let battle = Self::get_battle(env.clone(), name.clone());
+        // The round waits for the creator's committed seed
+        if Self::seed_unrevealed(&env, &battle) {
+            return;
+        }
+        if battle.players.len() > 2 {
+            return Self::resolve_free_for_all(env, name);
+        }
+        let user_1 = battle
+            .players
+            .keys()
+            .get(0)
+            .unwrap_or(env.current_contract_address());
+        let user_2 = battle
+            .players
+            .keys()
+            .get(1)
+            .unwrap_or(env.current_contract_address());
+
+        assert!(
+            battle.moves.get(user_1.clone()).unwrap_or(0) != 0
+                && battle.moves.get(user_2.clone()).unwrap_or(0) != 0,
+            "Players have not made their moves yet"
+        );
+
+        // Resolve battle
+        let user_1_move = battle.moves.get(user_1.clone()).unwrap_or(0);
+        let user_2_move = battle.moves.get(user_2.clone()).unwrap_or(0);
+
+        let user_1_stats = Self::get_player_stats(env.clone(), user_1.clone());
+        let user_2_stats = Self::get_player_stats(env.clone(), user_2.clone());
+
+        let user_1_attack = Self::effective_attack(&env, &battle, &user_1, &user_1_stats);
+        let user_2_attack = Self::effective_attack(&env, &battle, &user_2, &user_2_stats);
+
+        let user_1_health = user_1_stats.health;
+        let user_2_health = user_2_stats.health;
+
+        let user_1_special = Self::active_special(&battle, &user_1, &user_1_stats);
+        let user_2_special = Self::active_special(&battle, &user_2, &user_2_stats);
+
+        let mut user_1_damage = 0;
+        let mut user_2_damage = 0;
+        if user_1_move == 2 && user_2_move == 2 {
+            let user_1_heal = Self::heal_amount(user_1_stats.sword_class);
+            let user_2_heal = Self::heal_amount(user_2_stats.sword_class);
+            Self::increase_health(env.clone(), user_1.clone(), user_1_heal);
+            Self::increase_health(env.clone(), user_2.clone(), user_2_heal);
+        } else {
+            user_1_damage = Self::special_hit(
+                Self::hit(
+                    user_2_attack,
+                    Self::special_defense(user_1_stats.defense, user_2_special),
+                    user_2_move,
+                    user_1_move,
+                ),
+                user_2_special,
+                user_1_special,
+            );
+            user_2_damage = Self::special_hit(
+                Self::hit(
+                    user_1_attack,
+                    Self::special_defense(user_2_stats.defense, user_1_special),
+                    user_1_move,
+                    user_2_move,
+                ),
+                user_1_special,
+                user_2_special,
+            );
+
+            if user_1_damage >= user_1_health && user_2_damage >= user_2_health {
+                // When both would fall, the player with more defense, then more
+                // health, hangs on at 1 HP and wins
+                let user_1_edge = (user_1_stats.defense, user_1_health);
+                let user_2_edge = (user_2_stats.defense, user_2_health);
+                match user_1_edge.cmp(&user_2_edge) {
+                    Ordering::Greater => {
+                        Self::end_battle(env.clone(), name.clone(), user_1.clone())
+                    }
+                    Ordering::Less => Self::end_battle(env.clone(), name.clone(), user_2.clone()),
+                    Ordering::Equal => Self::end_battle_in_draw(env.clone(), name.clone()),
+                }
+            } else if user_2_damage >= user_2_health {
+                Self::end_battle(env.clone(), name.clone(), user_1.clone());
+            } else if user_1_damage >= user_1_health {
+                Self::end_battle(env.clone(), name.clone(), user_2.clone());
+            } else {
+                let _ = Self::decrease_health(env.clone(), user_1.clone(), user_1_damage);
+                let _ = Self::decrease_health(env.clone(), user_2.clone(), user_2_damage);
+            }
+        }
+
+        // Reload the battle, since end_battle may have already updated it
+        let mut battle = Self::get_battle(env.clone(), name.clone());
+        let health_after = |user: &Address, health_before: u32, damage: u32| {
+            if battle.battle_status != BattleStatus::Ended as u64 {
+                Self::get_player_stats(env.clone(), user.clone()).health
+            } else if battle.winner == OptionalAddress::Some(user.clone()) {
+                health_before.saturating_sub(damage).max(1)
+            } else {
+                0
+            }
+        };
+        let user_1_after = health_after(&user_1, user_1_health, user_1_damage);
+        let user_2_after = health_after(&user_2, user_2_health, user_2_damage);
+        let user_1_taken = user_1_health.saturating_sub(user_1_after);
+        let user_2_taken = user_2_health.saturating_sub(user_2_after);
+        let round = Round {
+            moves: map![
+                &env,
+                (user_1.clone(), user_1_move),
+                (user_2.clone(), user_2_move)
+            ],
+            health: map![
+                &env,
+                (user_1.clone(), user_1_after),
+                (user_2.clone(), user_2_after)
+            ],
+            dealt: map![
+                &env,
+                (user_1.clone(), user_2_taken),
+                (user_2.clone(), user_1_taken)
+            ],
+            taken: map![
+                &env,
+                (user_1.clone(), user_1_taken),
+                (user_2.clone(), user_2_taken)
+            ],
+        };
+        Self::record_damage_taken(&env, &round);
+        battle.rounds.push_back(round);
+        battle.round_count += 1;
+        battle.moves = map![&env, (user_1.clone(), 0), (user_2.clone(), 0)];
+        battle.first_mover = OptionalAddress::None;
+        for player in battle.specials.keys().iter() {
+            battle.specials.set(player, false);
+        }
+        battle.turns = 0;
+        let reached_cap =
+            battle.battle_status != BattleStatus::Ended as u64 && battle.round_count >= MAX_ROUNDS;
+        let _ = Self::set_battle(env.clone(), name.clone(), battle);
+
+        // A battle that hits the round cap goes to the player with more
+        // health left
+        if reached_cap {
+            match user_1_after.cmp(&user_2_after) {
+                Ordering::Greater => Self::end_battle(env.clone(), name, user_1),
+                Ordering::Less => Self::end_battle(env.clone(), name, user_2),
+                Ordering::Equal => Self::end_battle_in_draw(env.clone(), name),
+            }
+        }
+    }
+
+    fn end_battle(env: Env, name: Symbol, winner: Address) {
+        let mut battle = Self::get_battle(env.clone(), name.clone());
+        battle.battle_status = 2;
+        battle.winner = OptionalAddress::Some(winner.clone());
+        battle.result = Self::win_result(&battle);
+
+        let user_1 = battle
+            .players
+            .keys()
+            .get(0)
+            .unwrap_or(env.current_contract_address());
+        let user_2 = battle
+            .players
+            .keys()
+            .get(1)
+            .unwrap_or(env.current_contract_address());
+
+        // Resolve battle
+        let _user_1_move = battle.moves.get(user_1.clone()).unwrap_or(0);
+        let _user_2_move = battle.moves.get(user_2.clone()).unwrap_or(0);
+
+        Self::cleanup_battle_participants(env.clone(), &battle);
+        Self::settle_wager(&env, &battle, Some(winner.clone()));
+        Self::archive_battle(&env, name.clone());
+        Self::log_battle(&env, &battle);
+        let mut user_1_stats = Self::get_player_stats(env.clone(), user_1.clone());
+        let mut user_2_stats = Self::get_player_stats(env.clone(), user_2.clone());
+        battle.turns = 0;
+
+        let (winner_stats, loser_stats) = if winner == user_1 {
+            (&mut user_1_stats, &mut user_2_stats)
+        } else {
+            (&mut user_2_stats, &mut user_1_stats)
+        };
+        Self::record_win(&env, winner_stats);
+        Self::record_loss(loser_stats);
+
+        // Battles against the bot are unrated and pay a smaller reward. The
+        // bot itself never gains experience, so it can't level up.
         let contract_id = env.current_contract_address();
+        let rewards = Self::get_rewards(env.clone());
+        let reward = if user_1 != contract_id && user_2 != contract_id {
+            update_ratings(winner_stats, loser_stats);
+            rewards.pvp_win
+        } else {
+            rewards.bot_win
+        };
+        if winner_stats.player_address != contract_id {
+            Self::award_experience(&env, winner_stats, reward);
+        }
+        if loser_stats.player_address != contract_id {
+            Self::award_experience(&env, loser_stats, LOSS_EXPERIENCE);
+        }
+
+        for (user, stats) in [(user_1, user_1_stats), (user_2, user_2_stats)] {
+            if user != contract_id {
+                let _ = Self::set_player_stats(env.clone(), user, stats);
+            }
+        }
+        let _ = Self::set_battle(env.clone(), name.clone(), battle);
+        events::battle_ended(&env, name, winner);
+    }
+
+    /// A private function to resolve a round of a battle with more than two
+    /// players. Every attacker hits the opponent with the lowest health, the
+    /// first in player order on ties, and a defender's defense absorbs part of
+    /// each hit. If nobody attacks, everyone heals. Players knocked out are
+    /// eliminated, and the last one standing wins. Once only two players
+    /// remain, the usual two-player rules apply. Free-for-all results are unrated.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    fn resolve_free_for_all(env: Env, name: Symbol) {
         let mut battle = Self::get_battle(env.clone(), name.clone());
-        assert!(battle.battle_status == 0, "Battle already started");
-        let mut player = Self::get_player_stats(env.clone(), user.clone());
-        assert!(!player.in_battle, "Player already in battle");
+        let players = battle.players.keys();
+        let mut moves = Vec::new(&env);
+        let mut stats = Vec::new(&env);
+        let mut damage = Vec::new(&env);
+        let mut dealt = Vec::new(&env);
+        for player in players.iter() {
+            let choice = battle.moves.get(player.clone()).unwrap_or(0);
+            assert!(choice != 0, "Players have not made their moves yet");
+            moves.push_back(choice);
+            stats.push_back(Self::get_player_stats(env.clone(), player));
+            damage.push_back(0u32);
+            dealt.push_back(0u32);
+        }
 
-        let players = battle.players.clone();
-        let player_1 = players.keys().get(0).unwrap_or(contract_id.clone());
-        battle.players = map![&env, (player_1.clone(), 1), (user.clone(), 2)];
-        battle.moves = map![&env, (player_1.clone(), 0), (user.clone(), 0)];
-        battle.battle_status = 1;
-        player.in_battle = true;
+        let count = players.len();
+        for attacker in 0..count {
+            if moves.get_unchecked(attacker) == 2 {
+                continue;
+            }
+            let target = (0..count)
+                .filter(|target| *target != attacker)
+                .min_by_key(|target| stats.get_unchecked(*target).health)
+                .unwrap_or(attacker);
+            let attack = Self::effective_attack(
+                &env,
+                &battle,
+                &players.get_unchecked(attacker),
+                &stats.get_unchecked(attacker),
+            );
+            let hit = Self::hit(
+                attack,
+                stats.get_unchecked(target).defense,
+                moves.get_unchecked(attacker),
+                moves.get_unchecked(target),
+            );
+            damage.set(target, damage.get_unchecked(target) + hit);
+            dealt.set(attacker, dealt.get_unchecked(attacker) + hit);
+        }
+        let nobody_attacked = moves.iter().all(|choice| choice == 2);
 
-        // battle = Battle {
-        //     battle_status: 1,
-        //     name: name.clone(),
-        //     players: map![&env, (player_1, 1), (user.clone(), 2)],
-        //     moves: map![&env, (player_1, 0), (user.clone(), 0)],
-        //     winner: contract_id.clone(),
-        // };
-        let response: (Result<(), Error>, Result<(), Error>) = (
-            Self::set_battle(env.clone(), name.clone(), battle.clone()),
-            Self::set_player_stats(env.clone(), user.clone(), player.clone()),
-        );
+        let mut survivors = Vec::new(&env);
+        let mut knocked_out = Vec::new(&env);
+        let mut round = Round {
+            moves: map![&env],
+            health: map![&env],
+            dealt: map![&env],
+            taken: map![&env],
+        };
+        for index in 0..count {
+            let player = players.get_unchecked(index);
+            let mut stat = stats.get_unchecked(index);
+            if nobody_attacked {
+                let heal = Self::heal_amount(stat.sword_class);
+                Self::apply_heal(&mut stat, heal);
+            }
+            let health_before = stat.health;
+            stat.health = stat.health.saturating_sub(damage.get_unchecked(index));
+            round.moves.set(player.clone(), moves.get_unchecked(index));
+            round.health.set(player.clone(), stat.health);
+            round.dealt.set(player.clone(), dealt.get_unchecked(index));
+            round
+                .taken
+                .set(player.clone(), health_before.saturating_sub(stat.health));
+            if stat.health == 0 {
+                knocked_out.push_back(player);
+            } else {
+                survivors.push_back(player.clone());
+                let _ = Self::set_player_stats(env.clone(), player, stat);
+            }
+        }
+        Self::record_damage_taken(&env, &round);
+        let health_left = round.health.clone();
+        battle.rounds.push_back(round);
+        battle.round_count += 1;
+        battle.first_mover = OptionalAddress::None;
+        battle.turns = 0;
 
-        assert!(response == (Ok(()), Ok(())), "Error joining battle");
+        // A battle that hits the round cap goes to the player with the most
+        // health left, and is drawn between the players tied for it
+        let reached_cap = survivors.len() > 1 && battle.round_count >= MAX_ROUNDS;
+        if survivors.len() <= 1 || reached_cap {
+            let mut leaders = survivors.clone();
+            if reached_cap {
+                let health = |player: &Address| health_left.get(player.clone()).unwrap_or(0);
+                let top = survivors.iter().map(|player| health(&player)).max();
+                leaders = Vec::new(&env);
+                for player in survivors.iter() {
+                    if Some(health(&player)) == top {
+                        leaders.push_back(player);
+                    }
+                }
+            }
+            // Everyone falling in the same round draws
+            if leaders.is_empty() {
+                leaders = knocked_out.clone();
+            }
+            let mut losers = Vec::new(&env);
+            for player in players.iter() {
+                if !leaders.contains(player.clone()) {
+                    losers.push_back(player);
+                }
+            }
+            battle.battle_status = BattleStatus::Ended as u64;
+            battle.is_draw = leaders.len() != 1;
+            battle.result = BattleResult::Draw;
+            if !battle.is_draw {
+                battle.winner = leaders.first().into();
+                battle.result = Self::win_result(&battle);
+            }
+            let _ = Self::set_battle(env.clone(), name.clone(), battle.clone());
+            Self::cleanup_battle_participants(env.clone(), &battle);
+            Self::archive_battle(&env, name.clone());
+
+            for player in losers.iter() {
+                let mut stat = Self::get_player_stats(env.clone(), player.clone());
+                Self::record_loss(&mut stat);
+                Self::award_experience(&env, &mut stat, LOSS_EXPERIENCE);
+                let _ = Self::set_player_stats(env.clone(), player, stat);
+            }
+            if battle.is_draw {
+                for player in leaders.iter() {
+                    let mut stat = Self::get_player_stats(env.clone(), player.clone());
+                    stat.draws += 1;
+                    let _ = Self::set_player_stats(env.clone(), player, stat);
+                }
+                events::battle_drawn(&env, name);
+                return;
+            }
+            Self::record_free_for_all_win(&env, name, &battle);
+            return;
+        }
+
+        for player in knocked_out.iter() {
+            Self::eliminate_player(&env, &mut battle, player);
+        }
+        for player in survivors.iter() {
+            battle.moves.set(player, 0);
+        }
+        let _ = Self::set_battle(env.clone(), name, battle);
+    }
+
+    /// A private function to take players out of a free-for-all battle that
+    /// goes on without them, such as a player who forfeits. The last player
+    /// left wins, and otherwise the round resolves once everyone left has
+    /// moved.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    /// * `leavers` - The players leaving the battle.
+    fn leave_free_for_all(env: Env, name: Symbol, leavers: Vec<Address>) {
+        let mut battle = Self::get_battle(env.clone(), name.clone());
+        for player in leavers.iter() {
+            Self::eliminate_player(&env, &mut battle, player);
+        }
+        battle.turns = battle.moves.values().iter().filter(|m| *m != 0).count() as u64;
+
+        if battle.players.len() == 1 {
+            battle.battle_status = BattleStatus::Ended as u64;
+            battle.winner = battle.players.keys().first().into();
+            battle.result = Self::win_result(&battle);
+            battle.turns = 0;
+            let _ = Self::set_battle(env.clone(), name.clone(), battle.clone());
+            Self::cleanup_battle_participants(env.clone(), &battle);
+            Self::archive_battle(&env, name.clone());
+            return Self::record_free_for_all_win(&env, name, &battle);
+        }
+
+        let everyone_moved = battle.moves.values().iter().all(|choice| choice != 0);
+        let _ = Self::set_battle(env.clone(), name.clone(), battle);
+        if everyone_moved {
+            Self::await_battle_results(env.clone(), name, env.current_contract_address());
+        }
+    }
 
-        response
+    /// A private function to remove a player from a free-for-all battle that
+    /// goes on without them, booking their loss.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `battle` - The battle the player leaves.
+    /// * `player` - The player to remove.
+    fn eliminate_player(env: &Env, battle: &mut Battle, player: Address) {
+        // A departing creator's seed can no longer be revealed
+        if battle.players.get(player.clone()) == Some(1) {
+            battle.seed_commit = BytesN::from_array(env, &[0; 32]);
+        }
+        battle.players.remove(player.clone());
+        battle.moves.remove(player.clone());
+        let mut stat = Self::get_player_stats(env.clone(), player.clone());
+        stat.in_battle = false;
+        stat.current_battle = Symbol::new(env, "");
+        stat.health = Self::effective_stats(&stat).health;
+        Self::record_loss(&mut stat);
+        Self::award_experience(env, &mut stat, LOSS_EXPERIENCE);
+        let _ = Self::set_player_stats(env.clone(), player, stat);
     }
 
-    /// Joins an auto battle.
+    /// A private function to book the win of a free-for-all battle's winner.
     ///
     /// # Arguments
     ///
     /// * `env` - The contract execution environment.
     /// * `name` - The name of the battle.
-    /// * `user` - The address of the player joining the battle.
-    pub fn challenge_bot(env: Env, user: Address, name: Symbol) -> Result<(), Error> {
-        let mut battle = Self::get_battle(env.clone(), name.clone());
-        assert!(battle.battle_status == 0, "Battle already started");
-        let contract_id = env.current_contract_address();
+    /// * `battle` - The battle that was won.
+    fn record_free_for_all_win(env: &Env, name: Symbol, battle: &Battle) {
+        let OptionalAddress::Some(winner) = battle.winner.clone() else {
+            return;
+        };
+        let mut stat = Self::get_player_stats(env.clone(), winner.clone());
+        Self::record_win(env, &mut stat);
+        Self::award_experience(env, &mut stat, Self::get_rewards(env.clone()).pvp_win);
+        let _ = Self::set_player_stats(env.clone(), winner.clone(), stat);
+        events::battle_ended(env, name, winner);
+    }
 
-        battle.players = map![&env, (user.clone(), 1), (contract_id.clone(), 2)];
-        battle.battle_status = 1;
+    /// The result of a battle won by `battle.winner`, by the winner's slot.
+    fn win_result(battle: &Battle) -> BattleResult {
+        if OptionalAddress::from(Self::player_in_slot(battle, 1)) == battle.winner {
+            BattleResult::Player1Win
+        } else {
+            BattleResult::Player2Win
+        }
+    }
 
-        Self::set_battle(env.clone(), name.clone(), battle)
+    /// Records a win on a player's statistics.
+    fn record_win(env: &Env, stat: &mut PlayerStat) {
+        stat.wins += 1;
+        stat.streak = stat.streak.max(0) + 1;
+        if stat.wins == 1 {
+            stat.first_win_at = OptionalTimestamp::Some(env.ledger().timestamp());
+        }
     }
 
-    /// Sets a battle.
+    /// Adds experience to a player's statistics, levelling them up for every
+    /// `EXPERIENCE_PER_LEVEL` crossed. Each new level permanently raises
+    /// their attack and defense. Levels keep counting once a stat is capped;
+    /// only the stat stops, which is reported with a `stat_capped` event.
     ///
     /// # Arguments
     ///
     /// * `env` - The contract execution environment.
-    /// * `name` - The name of the battle.
-    /// * `battle` - The Battle struct containing the battle information.
-    fn set_battle(env: Env, name: Symbol, battle: Battle) -> Result<(), Error> {
-        env.storage()
-            .instance()
-            .set(&DataKey::Battle(name.clone()), &battle);
+    /// * `stat` - The statistics of the player.
+    /// * `amount` - The experience to award.
+    fn award_experience(env: &Env, stat: &mut PlayerStat, amount: u32) {
+        stat.experience = stat.experience.saturating_add(amount);
+        let level = 1 + stat.experience / EXPERIENCE_PER_LEVEL;
+        while stat.level < level {
+            stat.level += 1;
+            if Self::increase_attack(stat, LEVEL_ATTACK_BONUS).is_err() {
+                events::stat_capped(env, stat.player_address.clone(), symbol_short!("attack"));
+            }
+            if Self::increase_defense(stat, LEVEL_DEFENSE_BONUS).is_err() {
+                events::stat_capped(env, stat.player_address.clone(), symbol_short!("defense"));
+            }
+        }
+    }
 
-        env.storage().instance().bump(100, 100);
-        Ok(())
+    /// Adds the health each player lost in a round to their lifetime total.
+    fn record_damage_taken(env: &Env, round: &Round) {
+        for (player, taken) in round.taken.iter() {
+            if taken == 0 || player == env.current_contract_address() {
+                continue;
+            }
+            let mut stat = Self::get_player_stats(env.clone(), player.clone());
+            stat.total_damage_taken += taken as u64;
+            let _ = Self::set_player_stats(env.clone(), player, stat);
+        }
     }
 
-    /// Gets a battle.
+    /// Records a loss on a player's statistics.
+    fn record_loss(stat: &mut PlayerStat) {
+        stat.losses += 1;
+        stat.streak = stat.streak.min(0) - 1;
+    }
+
+    /// A player's attack after the configured decay for the rounds already
+    /// fought. Decay never takes a player's attack below 1.
     ///
     /// # Arguments
     ///
     /// * `env` - The contract execution environment.
-    /// * `name` - The name of the battle.
+    /// * `attack` - The player's stored attack.
+    /// * `rounds` - The number of rounds resolved so far.
+    fn decayed_attack(env: &Env, attack: u32, rounds: u32) -> u32 {
+        let lost = Self::get_attack_decay(env.clone())
+            .saturating_mul(rounds)
+            .min(100);
+        (attack * (100 - lost) / 100).max(attack.min(1))
+    }
+
+    /// Scales an attack by a pseudo-random factor between 80% and 120%, drawn
+    /// from the ledger, the battle's seed, the round and the attacker's slot.
+    /// The result is reproducible for a given ledger state.
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// A Battle struct containing the battle information.
-    pub fn get_battle(env: Env, name: Symbol) -> Battle {
-        let contract_id = env.current_contract_address();
-        env.storage()
-            .instance()
-            .get(&DataKey::Battle(name.clone()))
-            .unwrap_or(Battle {
-                battle_status: 0,
-                name: name.clone(),
-                players: map![&env, (contract_id.clone(), 1), (contract_id.clone(), 2)],
-                moves: map![&env, (contract_id.clone(), 0), (contract_id.clone(), 0)],
-                turns: 0,
-                winner: env.current_contract_address(),
-            })
+    /// * `env` - The contract execution environment.
+    /// * `seed` - The battle's seed, from `battle_seed`.
+    /// * `round` - The number of rounds resolved so far.
+    /// * `slot` - The attacker's slot in the battle.
+    /// * `attack` - The attack to scale.
+    fn damage_variance(env: &Env, seed: u64, round: u32, slot: u64, attack: u32) -> u32 {
+        let roll = rng::next_u64(env, seed ^ slot, round as u64);
+        attack * (80 + (roll % 41) as u32) / 100
     }
 
-    /// Sets the list of battles.
+    /// Rolls for a critical hit, which deals half as much damage again. The
+    /// chance depends on the attacker's sword class, and high rolls crit.
     ///
     /// # Arguments
     ///
     /// * `env` - The contract execution environment.
-    /// * `battles` - The Vec<Symbol> containing the names of the battles.
-    fn set_battles(env: Env, battles: Vec<Symbol>) -> Result<(), Error> {
-        env.storage().instance().set(&DataKey::Battles, &battles);
-        env.storage().instance().bump(100, 100);
-        Ok(())
+    /// * `seed` - The battle's seed, from `battle_seed`.
+    /// * `round` - The number of rounds resolved so far.
+    /// * `slot` - The attacker's slot in the battle.
+    /// * `class` - The attacker's sword class, or 0 without a sword.
+    /// * `attack` - The attack to scale.
+    fn critical_hit(env: &Env, seed: u64, round: u32, slot: u64, class: u32, attack: u32) -> u32 {
+        // Draw from a different stream than the damage variance
+        let roll = rng::next_u64(env, seed ^ slot, round as u64 | 1 << 32);
+        if roll % 100 >= 100 - Self::crit_chance(class) as u64 {
+            attack * 3 / 2
+        } else {
+            attack
+        }
     }
 
-    /// Gets the list of battles.
+    /// The percent chance a sword class lands a critical hit. Enhanced swords
+    /// crit as often as the class they were fused from.
     ///
     /// # Arguments
     ///
-    /// * `env` - The contract execution environment.
+    /// * `class` - The sword class, or 0 without a sword.
+    fn crit_chance(class: u32) -> u32 {
+        match class {
+            1 | 4 => 5,
+            2 | 5 => 25,
+            3 | 6 => 15,
+            _ => 0,
+        }
+    }
+
+    /// The health a player regenerates in a round where nobody attacks.
+    /// Longsword wielders recover the most, Sabre wielders the least.
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// A Vec<Symbol> containing the names of the battles.
-    pub fn get_battles(env: Env) -> Vec<Symbol> {
-        env.storage()
-            .instance()
-            .get(&DataKey::Battles)
-            .unwrap_or(Vec::new(&env))
+    /// * `class` - The player's sword class, or 0 without a sword.
+    fn heal_amount(class: u32) -> u32 {
+        match class {
+            1 | 4 => 4,
+            2 | 5 => 1,
+            3 | 6 => 3,
+            _ => 2,
+        }
     }
 
-    /// Handles player's attack or defend choice in a battle.
+    /// The damage a hit deals. Defense always softens a hit, and blocks it
+    /// fully when the target chose to defend. A hit on a target that didn't
+    /// defend always deals at least 1 damage, so battles can't stall.
     ///
     /// # Arguments
     ///
-    /// * `env` - The contract execution environment.
-    /// * `user` - The address of the player making the choice.
-    /// * `choice` - The choice made by the player.
-    /// * `battle_name` - The name of the battle in which the choice is made.
-    pub fn attack_or_defend_choice(env: Env, user: Address, choice: u64, battle_name: Symbol) {
-        user.require_auth();
-        assert!(choice == 1 || choice == 2, "Invalid choice");
-        let battle = Self::get_battle(env.clone(), battle_name.clone());
+    /// * `attack` - The attacker's effective attack.
+    /// * `defense` - The target's defense.
+    /// * `defending` - Whether the target chose to defend this round.
+    fn damage(attack: u32, defense: u32, defending: bool) -> u32 {
+        if defending {
+            attack.saturating_sub(defense)
+        } else {
+            attack.saturating_sub(defense / 2).max(attack.min(1))
+        }
+    }
 
-        assert!(
-            battle.battle_status == 1,
-            "Battle not started. Please tell another player to join the battle"
-        ); // Require that battle has started
-        assert!(
-            battle.players.contains_key(user.clone()),
-            "You are not in this battle"
-        ); // Require that player is in the battle
-        assert_eq!(
-            battle.moves.get(user.clone()).unwrap_or(0),
-            0,
-            "You have already made your move"
-        ); // Require that player has not made a move yet
+    /// The damage an attacker's move deals to a target given the target's
+    /// move. A light attack is blocked by a defense, a heavy attack hits
+    /// harder and breaks through a defense but is interrupted by a light
+    /// attack, and defending deals no damage.
+    ///
+    /// # Arguments
+    ///
+    /// * `attack` - The attacker's effective attack.
+    /// * `defense` - The target's defense.
+    /// * `attacker_move` - The attacker's move this round.
+    /// * `target_move` - The target's move this round.
+    fn hit(attack: u32, defense: u32, attacker_move: u64, target_move: u64) -> u32 {
+        match (attacker_move, target_move) {
+            (1, target_move) => Self::damage(attack, defense, target_move == 2),
+            (3, 1) => 0,
+            (3, _) => Self::damage(attack * HEAVY_ATTACK_PERCENT / 100, defense, false),
+            _ => 0,
+        }
+    }
 
-        let mut battle = Self::get_battle(env.clone(), battle_name.clone());
-        battle.turns += 1;
-        battle.moves.set(user.clone(), choice);
+    /// The sword class whose special a player has waiting to take effect this
+    /// round, with enhanced classes mapped to the class they were fused from,
+    /// or 0 if the player has no special waiting.
+    ///
+    /// # Arguments
+    ///
+    /// * `battle` - The battle being resolved.
+    /// * `player` - The player.
+    /// * `stat` - The player's statistics.
+    fn active_special(battle: &Battle, player: &Address, stat: &PlayerStat) -> u32 {
+        if !stat.has_sword || !battle.specials.get(player.clone()).unwrap_or(false) {
+            return 0;
+        }
+        (stat.sword_class + ENHANCED_CLASS_OFFSET - 1) % ENHANCED_CLASS_OFFSET + 1
+    }
 
-        let _ = Self::set_battle(env.clone(), battle_name.clone(), battle.clone());
-        let battle_turns = battle.turns.clone();
+    /// The defense a target has against an attacker, which a Claymore's Cleave
+    /// ignores.
+    ///
+    /// # Arguments
+    ///
+    /// * `defense` - The target's defense.
+    /// * `attacker_special` - The attacker's active special, or 0.
+    fn special_defense(defense: u32, attacker_special: u32) -> u32 {
+        if attacker_special == 3 {
+            0
+        } else {
+            defense
+        }
+    }
 
-        if battle_turns == 2 {
-            Self::await_battle_results(env.clone(), battle_name.clone(), user.clone());
+    /// A hit after specials: a Sabre's Flurry doubles it, and a Longsword's
+    /// Parry negates it.
+    ///
+    /// # Arguments
+    ///
+    /// * `hit` - The damage of the hit.
+    /// * `attacker_special` - The attacker's active special, or 0.
+    /// * `target_special` - The target's active special, or 0.
+    fn special_hit(hit: u32, attacker_special: u32, target_special: u32) -> u32 {
+        match (attacker_special, target_special) {
+            (_, 1) => 0,
+            (2, _) => hit * 2,
+            _ => hit,
         }
-        // Self::await_battle_results(env.clone(), battle_name.clone(), user.clone());
     }
 
-    /// A private function to await battle results.
+    /// A player's attack for the current round of a battle, after decay,
+    /// damage variance and critical hits.
     ///
     /// # Arguments
     ///
     /// * `env` - The contract execution environment.
-    /// * `name` - The name of the battle.
-    /// * `user` - The address of the user.
-    fn await_battle_results(env: Env, name: Symbol, _user: Address) {
-        let mut battle = Self::get_battle(env.clone(), name.clone());
-        let user_1 = battle
-            .players
-            .keys()
-            .get(0)
-            .unwrap_or(env.current_contract_address());
-        let user_2 = battle
-            .players
-            .keys()
-            .get(1)
-            .unwrap_or(env.current_contract_address());
-
-        assert!(
-            battle.moves.get(user_1.clone()).unwrap_or(0) != 0
-                && battle.moves.get(user_2.clone()).unwrap_or(0) != 0,
-            "Players have not made their moves yet"
-        );
-
-        // Resolve battle
-        let user_1_move = battle.moves.get(user_1.clone()).unwrap_or(0);
-        let user_2_move = battle.moves.get(user_2.clone()).unwrap_or(0);
-
-        let mut user_1_stats = Self::get_player_stats(env.clone(), user_1.clone());
-        let mut user_2_stats = Self::get_player_stats(env.clone(), user_2.clone());
+    /// * `battle` - The battle being resolved.
+    /// * `player` - The attacking player.
+    /// * `stat` - The attacking player's statistics.
+    fn effective_attack(env: &Env, battle: &Battle, player: &Address, stat: &PlayerStat) -> u32 {
+        let round = battle.rounds.len();
+        let slot = battle.players.get(player.clone()).unwrap_or(0);
+        let attack = Self::decayed_attack(env, stat.attack, round);
+        let seed = Self::battle_seed(env, battle);
+        let attack = Self::damage_variance(env, seed, round, slot, attack);
+        Self::critical_hit(env, seed, round, slot, stat.sword_class, attack)
+    }
 
-        let user_1_attack = user_1_stats.attack;
-        let user_2_attack = user_2_stats.attack;
+    /// A seed derived from a battle's name, so different battles draw
+    /// different random values from the same ledger.
+    fn name_seed(env: &Env, name: &Symbol) -> u64 {
+        let hash = env.crypto().sha256(&name.clone().to_xdr(env)).to_array();
+        let mut seed = [0u8; 8];
+        seed.copy_from_slice(&hash[..8]);
+        u64::from_be_bytes(seed)
+    }
 
-        let user_1_defense = user_1_stats.defense;
-        let user_2_defense = user_2_stats.defense;
+    /// The seed a battle's damage variance and critical hits draw from: its
+    /// name's seed mixed with the creator's revealed seed, if any.
+    fn battle_seed(env: &Env, battle: &Battle) -> u64 {
+        Self::name_seed(env, &battle.name) ^ battle.revealed_seed
+    }
 
-        let user_1_health = user_1_stats.health;
-        let user_2_health = user_2_stats.health;
-        let user_2_phad: u32 = user_2_health + user_2_defense;
-        let user_1_phad: u32 = user_1_health + user_1_defense;
+    /// A private function to end a battle in which both players fell together.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    fn end_battle_in_draw(env: Env, name: Symbol) {
+        let mut battle = Self::get_battle(env.clone(), name.clone());
+        battle.battle_status = BattleStatus::Ended as u64;
+        battle.is_draw = true;
+        battle.result = BattleResult::Draw;
+        battle.turns = 0;
 
-        if user_1_move == 1 && user_2_move == 1 {
-            if user_1_attack >= user_2_health {
-                Self::end_battle(env.clone(), name.clone(), user_1.clone());
-            } else if user_2_attack >= user_1_health {
-                Self::end_battle(env.clone(), name.clone(), user_2.clone());
-            } else {
-                let _ = Self::decrease_health(env.clone(), user_1.clone(), user_2_attack);
-                let _ = Self::decrease_health(env.clone(), user_2.clone(), user_1_attack);
+        Self::cleanup_battle_participants(env.clone(), &battle);
+        Self::settle_wager(&env, &battle, None);
+        let contract_id = env.current_contract_address();
+        for player in battle.players.keys().iter() {
+            if player == contract_id {
+                continue;
             }
-        } else if user_1_move == 1 && user_2_move == 2 {
-            if user_1_attack >= user_2_phad {
-                Self::end_battle(env.clone(), name.clone(), user_1.clone());
-            } else {
-                let health_after_attack;
+            let mut stat = Self::get_player_stats(env.clone(), player.clone());
+            stat.draws += 1;
+            let _ = Self::set_player_stats(env.clone(), player, stat);
+        }
+        Self::log_battle(&env, &battle);
+        let _ = Self::set_battle(env.clone(), name.clone(), battle);
+        Self::archive_battle(&env, name.clone());
+        events::battle_drawn(&env, name);
+    }
 
-                if user_2_defense > user_1_attack {
-                    health_after_attack = user_2_health;
-                } else {
-                    health_after_attack = user_2_phad - user_1_attack;
-                }
-                // env.storage()
-                //     .instance()
-                //     .set(&user_2_stats.health, &health_after_attack)
-                user_2_stats.health = health_after_attack;
-                let _ = Self::set_player_stats(env.clone(), user_2.clone(), user_2_stats);
-            }
-        } else if user_1_move == 2 && user_2_move == 1 {
-            if user_2_attack >= user_1_phad {
-                Self::end_battle(env.clone(), name.clone(), user_2.clone());
-            } else {
-                let health_after_attack;
+    /// A private function to release a battle's players once it is over,
+    /// whichever way it ended.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `battle` - The battle that is over.
+    fn cleanup_battle_participants(env: Env, battle: &Battle) {
+        let contract_id = env.current_contract_address();
+        for player in battle.players.keys().iter() {
+            // The bot, or a pending battle's empty slot, has no record
+            if player == contract_id {
+                continue;
+            }
+            let mut stats = Self::get_player_stats(env.clone(), player.clone());
+            stats.in_battle = false;
+            stats.current_battle = Symbol::new(&env, "");
 
-                if user_1_defense > user_2_attack {
-                    health_after_attack = user_1_health;
-                } else {
-                    health_after_attack = user_1_phad - user_2_attack;
+            // Every battle that was fought wears the sword down until it
+            // breaks, and a broken sword is burned
+            if stats.has_sword && battle.round_count > 0 {
+                stats.durability = stats.durability.saturating_sub(1);
+                if stats.durability == 0 {
+                    let class = stats.sword_class;
+                    if SwordContract::balance_of(env.clone(), player.clone(), class) > 0 {
+                        let _ = Self::burn_sword(&env, &player, class);
+                    }
+                    stats.sword_class = 0;
+                    stats.has_sword = false;
+                    stats.attack = stats.base_attack;
+                    stats.defense = stats.base_defense;
                 }
-                //     env.storage()
-                //         .instance()
-                //         .set(&user_1_stats.health, &health_after_attack)
-                user_1_stats.health = health_after_attack;
-                let _ = Self::set_player_stats(env.clone(), user_1.clone(), user_1_stats);
             }
-        } else if user_1_move == 2 && user_2_move == 2 {
-            Self::increase_health(env.clone(), user_1.clone(), 2);
-            Self::increase_health(env.clone(), user_2.clone(), 2);
+            stats.health = Self::effective_stats(&stats).health;
+            let _ = Self::set_player_stats(env.clone(), player, stats);
         }
-
-        battle.moves = map![&env, (user_1.clone(), 0), (user_2.clone(), 0)];
-        battle.turns = 0;
-        let _ = Self::set_battle(env.clone(), name.clone(), battle);
     }
 
-    fn end_battle(env: Env, name: Symbol, winner: Address) {
-        let mut battle = Self::get_battle(env.clone(), name.clone());
-        battle.battle_status = 2;
-        battle.winner = winner.clone();
+    /// A private function to pay out a battle's wager once it is over: the
+    /// winner takes every stake, and without a winner each player gets their
+    /// stake back. The bot never stakes, so a win by the bot is refunded too.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `battle` - The battle that is over.
+    /// * `winner` - The winner of the battle, if it has one.
+    fn settle_wager(env: &Env, battle: &Battle, winner: Option<Address>) {
+        let wager = Self::get_wager(env.clone(), battle.name.clone());
+        if wager == 0 {
+            return;
+        }
+        env.storage()
+            .instance()
+            .remove(&DataKey::Wager(battle.name.clone()));
 
-        let user_1 = battle
-            .players
-            .keys()
-            .get(0)
-            .unwrap_or(env.current_contract_address());
-        let user_2 = battle
+        // Only players who joined have staked; a pending battle's placeholder
+        // slot belongs to the contract
+        let contract_id = env.current_contract_address();
+        let token = token::Client::new(env, &Self::get_wager_token(env.clone()));
+        let stakers = battle
             .players
             .keys()
-            .get(1)
-            .unwrap_or(env.current_contract_address());
+            .iter()
+            .filter(|player| *player != contract_id);
+        match winner.filter(|winner| *winner != contract_id) {
+            Some(winner) => {
+                let pot = wager * stakers.count() as i128;
+                token.transfer(&contract_id, &winner, &pot);
+            }
+            None => {
+                for player in stakers {
+                    token.transfer(&contract_id, &player, &wager);
+                }
+            }
+        }
+    }
 
-        // Resolve battle
-        let _user_1_move = battle.moves.get(user_1.clone()).unwrap_or(0);
-        let _user_2_move = battle.moves.get(user_2.clone()).unwrap_or(0);
+    /// A private function to move an ended battle from the battle list into
+    /// the archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `name` - The name of the battle.
+    fn archive_battle(env: &Env, name: Symbol) {
+        let mut battles = Self::get_battles(env.clone());
+        if let Some(index) = battles.first_index_of(name.clone()) {
+            battles.remove(index);
+        }
+        let _ = Self::set_battles(env.clone(), battles);
 
-        let mut user_1_stats = Self::get_player_stats(env.clone(), user_1.clone());
-        let mut user_2_stats = Self::get_player_stats(env.clone(), user_2.clone());
+        let mut archive = Self::get_battle_archive(env.clone());
+        archive.push_back(name);
+        env.storage()
+            .instance()
+            .set(&DataKey::BattleArchive, &archive);
+    }
 
-        user_1_stats.in_battle = false;
-        user_1_stats.health = 100;
+    /// A private function to append an ended two-player battle to the battle
+    /// log.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The contract execution environment.
+    /// * `battle` - The battle that ended.
+    fn log_battle(env: &Env, battle: &Battle) {
+        let mut log: Vec<BattleLogEntry> = env
+            .storage()
+            .instance()
+            .get(&DataKey::BattleLog)
+            .unwrap_or(Vec::new(env));
+        let players = Self::players_by_slot(env, battle);
+        log.push_back(BattleLogEntry {
+            name: battle.name.clone(),
+            player_a: players.get_unchecked(0),
+            player_b: players.get_unchecked(1),
+            winner: battle.winner.clone(),
+            is_draw: battle.is_draw,
+            ended_at: env.ledger().timestamp(),
+        });
+        env.storage().instance().set(&DataKey::BattleLog, &log);
+    }
 
-        user_2_stats.in_battle = false;
-        user_2_stats.health = 100;
-        battle.turns = 0;
+    /// The number of battles a freshly forged or repaired sword lasts.
+    ///
+    /// # Arguments
+    ///
+    /// * `class` - The sword class.
+    fn max_durability(class: u32) -> u32 {
+        match class {
+            2 | 5 => 15,
+            3 | 6 => 25,
+            _ => 20,
+        }
+    }
 
-        let _ = Self::set_player_stats(env.clone(), user_1.clone(), user_1_stats);
-        let _ = Self::set_battle(env.clone(), name.clone(), battle);
+    /// The maximum health a player can heal up to: their configured base
+    /// health plus their sword's health bonus.
+    ///
+    /// # Arguments
+    ///
+    /// * `stat` - The statistics of the player.
+    fn max_health(stat: &PlayerStat) -> u32 {
+        Self::effective_stats(stat).health
+    }
+
+    /// Heals a player, up to `HEAL_CAP_PERCENT` of their maximum health. Every
+    /// heal goes through here so the cap is applied the same way everywhere.
+    /// Healing never lowers a player who is already above the cap.
+    ///
+    /// # Arguments
+    ///
+    /// * `stat` - The statistics of the player to heal.
+    /// * `amount` - The health to restore.
+    fn apply_heal(stat: &mut PlayerStat, amount: u32) {
+        let cap = Self::max_health(stat) * HEAL_CAP_PERCENT / 100;
+        if stat.health < cap {
+            stat.health = stat.health.saturating_add(amount).min(cap);
+        }
     }
 
     fn increase_health(env: Env, user: Address, incr: u32) -> u32 {
         // Get the current count.
         let mut player_stat = Self::get_player_stats(env.clone(), user.clone());
 
-        // Increment the count.
-        player_stat.health += incr;
+        // Increment the count, up to the heal cap.
+        Self::apply_heal(&mut player_stat, incr);
 
         // Save the count.
         let _ = Self::set_player_stats(env.clone(), user.clone(), player_stat.clone());
@@ -651,8 +4360,8 @@ impl BattleContract {
         // Get the current count.
         let mut player_stat = Self::get_player_stats(env.clone(), user.clone());
 
-        // Increment the count.
-        player_stat.health -= decr;
+        // Decrement the count, stopping at zero.
+        player_stat.health = player_stat.health.saturating_sub(decr);
 
         // Save the count.
         let _ = Self::set_player_stats(env.clone(), user.clone(), player_stat.clone());
@@ -662,5 +4371,59 @@ impl BattleContract {
     }
 }
 
+/// Helpers that work on a player's statistics directly, outside the contract
+/// interface.
+impl BattleContract {
+    /// Raises a player's base and current attack, saturating at `MAX_ATTACK`
+    /// instead of overflowing.
+    ///
+    /// # Arguments
+    ///
+    /// * `stat` - The statistics of the player.
+    /// * `incr` - The attack to add.
+    ///
+    /// # Returns
+    ///
+    /// `StatCapReached` if the increase would take the attack past
+    /// `MAX_ATTACK`, in which case it stops at the cap.
+    pub fn increase_attack(stat: &mut PlayerStat, incr: u32) -> Result<(), Error> {
+        let raised = stat
+            .base_attack
+            .max(stat.base_attack.saturating_add(incr).min(MAX_ATTACK));
+        stat.attack = stat.attack.saturating_add(raised - stat.base_attack);
+        let capped = raised - stat.base_attack < incr;
+        stat.base_attack = raised;
+        if capped {
+            return Err(BattleError::StatCapReached.into());
+        }
+        Ok(())
+    }
+
+    /// Raises a player's base and current defense, saturating at `MAX_DEFENSE`
+    /// instead of overflowing.
+    ///
+    /// # Arguments
+    ///
+    /// * `stat` - The statistics of the player.
+    /// * `incr` - The defense to add.
+    ///
+    /// # Returns
+    ///
+    /// `StatCapReached` if the increase would take the defense past
+    /// `MAX_DEFENSE`, in which case it stops at the cap.
+    pub fn increase_defense(stat: &mut PlayerStat, incr: u32) -> Result<(), Error> {
+        let raised = stat
+            .base_defense
+            .max(stat.base_defense.saturating_add(incr).min(MAX_DEFENSE));
+        stat.defense = stat.defense.saturating_add(raised - stat.base_defense);
+        let capped = raised - stat.base_defense < incr;
+        stat.base_defense = raised;
+        if capped {
+            return Err(BattleError::StatCapReached.into());
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test;
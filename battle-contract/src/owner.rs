@@ -0,0 +1,55 @@
+use crate::storage_types::{NFTDataKey, BALANCE_BUMP_AMOUNT};
+use soroban_sdk::{map, Address, Env, Map};
+
+pub fn read_owner(e: &Env, token_id: u64) -> Option<Address> {
+    let key = NFTDataKey::Owner(token_id);
+    e.storage().persistent().get(&key)
+}
+
+// Token ids owned by an address, mapped to the sword class of each.
+pub fn read_owned_tokens(e: &Env, owner: Address) -> Map<u64, u32> {
+    let key = NFTDataKey::OwnedTokens(owner);
+    e.storage().persistent().get(&key).unwrap_or(map![e])
+}
+
+fn write_owned_tokens(e: &Env, owner: Address, tokens: &Map<u64, u32>) {
+    let key = NFTDataKey::OwnedTokens(owner);
+    e.storage().persistent().set(&key, tokens);
+    e.storage()
+        .persistent()
+        .bump(&key, BALANCE_BUMP_AMOUNT, BALANCE_BUMP_AMOUNT + 100);
+}
+
+pub fn assign_owner(e: &Env, to: Address, class: u32, amount: i128) {
+    let mut next_id: u64 = e
+        .storage()
+        .instance()
+        .get(&NFTDataKey::NextTokenId)
+        .unwrap_or(1);
+    let mut tokens = read_owned_tokens(e, to.clone());
+    for _ in 0..amount {
+        let key = NFTDataKey::Owner(next_id);
+        e.storage().persistent().set(&key, &to);
+        e.storage()
+            .persistent()
+            .bump(&key, BALANCE_BUMP_AMOUNT, BALANCE_BUMP_AMOUNT + 100);
+        tokens.set(next_id, class);
+        next_id += 1;
+    }
+    write_owned_tokens(e, to, &tokens);
+    e.storage()
+        .instance()
+        .set(&NFTDataKey::NextTokenId, &next_id);
+}
+
+// Clears the owner's oldest token of the given class.
+pub fn clear_owner(e: &Env, from: Address, class: u32) {
+    let mut tokens = read_owned_tokens(e, from.clone());
+    if let Some((token_id, _)) = tokens.iter().find(|(_, token_class)| *token_class == class) {
+        e.storage()
+            .persistent()
+            .remove(&NFTDataKey::Owner(token_id));
+        tokens.remove(token_id);
+    }
+    write_owned_tokens(e, from, &tokens);
+}
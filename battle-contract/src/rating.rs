@@ -0,0 +1,20 @@
+use crate::PlayerStat;
+
+pub const STARTING_RATING: u32 = 1200;
+pub(crate) const ELO_K_FACTOR: u32 = 32;
+
+// Linear approximation of the Elo expected score, so the rating change stays in
+// integer math: an even match moves K/2 points, and every 800 points of rating
+// difference shifts the change by a full K, clamped so a win always counts.
+fn rating_change(winner_rating: u32, loser_rating: u32) -> u32 {
+    let k = ELO_K_FACTOR as i64;
+    let diff = loser_rating as i64 - winner_rating as i64;
+    (k / 2 + diff * k / 800).clamp(1, k - 1) as u32
+}
+
+pub fn update_ratings(winner: &mut PlayerStat, loser: &mut PlayerStat) {
+    let change = rating_change(winner.rating, loser.rating);
+    winner.rating += change;
+    winner.peak_rating = winner.peak_rating.max(winner.rating);
+    loser.rating = loser.rating.saturating_sub(change);
+}
@@ -0,0 +1,37 @@
+use soroban_sdk::Env;
+#[cfg(test)]
+use soroban_sdk::{symbol_short, Symbol};
+
+#[cfg(test)]
+const OVERRIDE_KEY: Symbol = symbol_short!("RNG_OVR");
+
+// Finalizer from splitmix64, used to spread the mixed inputs over all 64 bits.
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// Every randomized mechanic draws from here. The value depends only on the
+// current ledger, the caller's seed and the round, so it is reproducible for a
+// given ledger state. It is not secure against validators choosing the ledger.
+pub fn next_u64(e: &Env, seed: u64, round: u64) -> u64 {
+    #[cfg(test)]
+    if let Some(value) = e.storage().instance().get(&OVERRIDE_KEY) {
+        return value;
+    }
+
+    let ledger = ((e.ledger().sequence() as u64) << 32) ^ e.ledger().timestamp();
+    mix(mix(ledger ^ seed) ^ round)
+}
+
+// Forces `next_u64` to return `value`, or restores normal behavior with `None`.
+// Must be called from within the contract's context.
+#[cfg(test)]
+pub fn set_override(e: &Env, value: Option<u64>) {
+    match value {
+        Some(value) => e.storage().instance().set(&OVERRIDE_KEY, &value),
+        None => e.storage().instance().remove(&OVERRIDE_KEY),
+    }
+}
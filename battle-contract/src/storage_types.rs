@@ -1,6 +1,8 @@
 use soroban_sdk::{contracttype, Address};
 
-// pub(crate) const INSTANCE_BUMP_AMOUNT: u32 = 34560; // 2 days
+pub(crate) const DAY_IN_LEDGERS: u32 = 17280;
+pub(crate) const INSTANCE_BUMP_AMOUNT: u32 = 7 * DAY_IN_LEDGERS; // 7 days
+pub(crate) const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
 pub(crate) const BALANCE_BUMP_AMOUNT: u32 = 518400; // 30 days
 
 #[derive(Clone)]
@@ -24,5 +26,10 @@ pub enum NFTDataKey {
     Nonce(Address),
     State(Address),
     NFTMetadata(u32),
+    Supply(u32),
+    Owner(u64),
+    OwnedTokens(Address),
+    NextTokenId,
     Admin,
+    Minter,
 }
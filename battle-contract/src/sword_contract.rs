@@ -1,18 +1,39 @@
-use crate::balance::{read_balance, receive_balance, spend_balance};
-use crate::storage_types::NFTDataKey;
-use soroban_sdk::{contract, contractimpl, contracttype, Address, Env, Error, String};
+use crate::balance::{
+    read_balance, read_supply, receive_balance, spend_balance, write_authorization, write_supply,
+};
+use crate::owner::{assign_owner, clear_owner, read_owner};
+use crate::storage_types::{NFTDataKey, INSTANCE_BUMP_AMOUNT, INSTANCE_LIFETIME_THRESHOLD};
+use soroban_sdk::{contract, contractimpl, contracttype, Address, Env, Error, Map, String, Vec};
 
 // This contract is meant to be used for educational purposes only.
 pub trait NFTCollectionFactory {
     // Admin interface – privileged functions.
-    fn initialize(env: Env, admin: Address);
+    fn initialize(env: Env, admin: Address, minter: Address); // The minter is usually the battle contract
 
-    fn mint_nft(env: Env, to: Address, token_id: u32, amount: i128) -> Result<(), Error>; // Returns the address of the minted NFT
+    fn admin(env: Env) -> Address; // Returns the collection admin
 
-    fn melt_blade(env: Env, from: Address, token_id: u32) -> Result<(), Error>;
+    fn set_authorized(env: Env, admin: Address, addr: Address, authorized: bool); // Freezes or unfreezes an address
+
+    fn mint_nft(
+        env: Env,
+        minter: Address,
+        to: Address,
+        token_id: u32,
+        amount: i128,
+    ) -> Result<(), Error>; // Only the admin or the authorized minter can mint
+
+    fn melt_blade(env: Env, minter: Address, from: Address, token_id: u32) -> Result<(), Error>;
 
     fn balance_of(env: Env, owner: Address, token_id: u32) -> i128;
 
+    fn balances_of(env: Env, owner: Address) -> Map<u32, i128>; // Returns the balance of every class
+
+    fn class_supply(env: Env) -> Vec<(u32, i128)>; // Returns the supply of each token id
+
+    fn total_supply(env: Env, token_id: u32) -> i128;
+
+    fn owner_of(env: Env, token_id: u64) -> Address; // token_id is the id assigned at mint, not the class
+
     // Descriptive Interface
     fn get_token_metadata(env: Env, token_id: u32) -> TokenMetadata;
 
@@ -23,9 +44,9 @@ pub trait NFTCollectionFactory {
 #[derive(Clone, Debug, Eq, PartialEq)]
 // NFTMetadata struct to hold NFT metadata, including descriptions and IPFS hashes.
 pub struct TokenMetadata {
-    token_uri: String, // IPFS hash or URL
-    name: String,
-    symbol: String,
+    pub token_uri: String, // IPFS hash or URL
+    pub name: String,
+    pub symbol: String,
 }
 
 #[contract]
@@ -39,10 +60,35 @@ impl NFTCollectionFactory for SwordContract {
         }
     }
 
-    fn initialize(env: Env, admin: Address) {
+    fn initialize(env: Env, admin: Address, minter: Address) {
+        if env.storage().instance().has(&NFTDataKey::Admin) {
+            panic!("already initialized");
+        }
         admin.require_auth();
         // Initialize the collection.
         env.storage().instance().set(&NFTDataKey::Admin, &admin);
+        env.storage().instance().set(&NFTDataKey::Minter, &minter);
+    }
+
+    fn admin(env: Env) -> Address {
+        // Get the admin set at initialization.
+        env.storage()
+            .instance()
+            .get(&NFTDataKey::Admin)
+            .expect("not initialized")
+    }
+
+    fn set_authorized(env: Env, admin: Address, addr: Address, authorized: bool) {
+        admin.require_auth();
+        let stored_admin: Address = env
+            .storage()
+            .instance()
+            .get(&NFTDataKey::Admin)
+            .expect("not initialized");
+        if admin != stored_admin {
+            panic!("only the admin can change authorization");
+        }
+        write_authorization(&env, addr, authorized);
     }
 
     fn balance_of(env: Env, owner: Address, token_id: u32) -> i128 {
@@ -52,52 +98,50 @@ impl NFTCollectionFactory for SwordContract {
         balance_amount.unwrap_or(0)
     }
 
-    fn mint_nft(env: Env, to: Address, token_id: u32, amount: i128) -> Result<(), Error> {
-        Self::check_nonnegative_amount(amount);
-
-        let new_token_uri: String = match token_id {
-            1 => String::from_slice(&env, "https://example/token0"),
-            2 => String::from_slice(&env, "https://example/token1"),
-            3 => String::from_slice(&env, "https://example/token2"),
-            _ => String::from_slice(&env, "https://example/token0"),
-        };
-
-        let _name: String = match token_id {
-            1 => String::from_slice(&env, "Longsword"),
-            2 => String::from_slice(&env, "Sabre"),
-            3 => String::from_slice(&env, "Claymore"),
-            _ => String::from_slice(&env, "Longsword"),
-        };
-
-        let _symbol: String = match token_id {
-            1 => String::from_slice(&env, "LS"),
-            2 => String::from_slice(&env, "S"),
-            3 => String::from_slice(&env, "C"),
-            _ => String::from_slice(&env, "LS"),
-        };
-
-        // Mint a new NFT.
-        let nft_metadata: TokenMetadata = TokenMetadata {
-            token_uri: new_token_uri,
-            name: _name,
-            symbol: _symbol,
-        };
-        let nft_metadata_key = NFTDataKey::NFTMetadata(token_id);
-        env.storage()
-            .instance()
-            .set(&nft_metadata_key, &nft_metadata);
+    fn balances_of(env: Env, owner: Address) -> Map<u32, i128> {
+        // Get the balance of every sword class, including those never held.
+        let mut balances = read_balance(&env, owner);
+        for token_id in 1..=6 {
+            if !balances.contains_key(token_id) {
+                balances.set(token_id, 0);
+            }
+        }
+        balances
+    }
+
+    fn mint_nft(
+        env: Env,
+        minter: Address,
+        to: Address,
+        token_id: u32,
+        amount: i128,
+    ) -> Result<(), Error> {
+        check_minter(&env, &minter);
+        mint(&env, to, token_id, amount)
+    }
+
+    fn melt_blade(env: Env, minter: Address, from: Address, token_id: u32) -> Result<(), Error> {
+        check_minter(&env, &minter);
+        burn(&env, from, token_id)
+    }
 
-        receive_balance(&env, to.clone(), token_id, amount);
-        env.storage().instance().bump(100, 100);
+    fn class_supply(env: Env) -> Vec<(u32, i128)> {
+        // Get the minted supply of every sword class.
+        let mut supply = Vec::new(&env);
+        for token_id in 1..=6 {
+            supply.push_back((token_id, read_supply(&env, token_id)));
+        }
+        supply
+    }
 
-        Ok(())
+    fn total_supply(env: Env, token_id: u32) -> i128 {
+        // Get the number of NFTs of a class minted and not yet melted.
+        read_supply(&env, token_id)
     }
 
-    fn melt_blade(env: Env, from: Address, token_id: u32) -> Result<(), Error> {
-        // Burn an NFT.
-        spend_balance(&env, from, token_id);
-        env.storage().instance().bump(100, 100);
-        Ok(())
+    fn owner_of(env: Env, token_id: u64) -> Address {
+        // Get the owner of a minted NFT.
+        read_owner(&env, token_id).expect("token does not exist")
     }
 
     fn get_token_metadata(env: Env, token_id: u32) -> TokenMetadata {
@@ -108,3 +152,88 @@ impl NFTCollectionFactory for SwordContract {
             .unwrap()
     }
 }
+
+// Panics unless the caller is the collection admin or its authorized minter.
+fn check_minter(env: &Env, caller: &Address) {
+    let admin: Address = env
+        .storage()
+        .instance()
+        .get(&NFTDataKey::Admin)
+        .expect("not initialized");
+    let minter: Option<Address> = env.storage().instance().get(&NFTDataKey::Minter);
+    if *caller != admin && Some(caller.clone()) != minter {
+        panic!("only the admin or minter can mint and melt");
+    }
+    caller.require_auth();
+}
+
+// Mints swords of a class without an authorization check, for the battle
+// contract's own forging and the authorized `mint_nft` entry point.
+pub(crate) fn mint(env: &Env, to: Address, token_id: u32, amount: i128) -> Result<(), Error> {
+    SwordContract::check_nonnegative_amount(amount);
+
+    let new_token_uri: String = match token_id {
+        1 => String::from_slice(env, "https://example/token0"),
+        2 => String::from_slice(env, "https://example/token1"),
+        3 => String::from_slice(env, "https://example/token2"),
+        4 => String::from_slice(env, "https://example/token3"),
+        5 => String::from_slice(env, "https://example/token4"),
+        6 => String::from_slice(env, "https://example/token5"),
+        _ => String::from_slice(env, "https://example/token0"),
+    };
+
+    let _name: String = match token_id {
+        1 => String::from_slice(env, "Longsword"),
+        2 => String::from_slice(env, "Sabre"),
+        3 => String::from_slice(env, "Claymore"),
+        4 => String::from_slice(env, "Enhanced Longsword"),
+        5 => String::from_slice(env, "Enhanced Sabre"),
+        6 => String::from_slice(env, "Enhanced Claymore"),
+        _ => String::from_slice(env, "Longsword"),
+    };
+
+    let _symbol: String = match token_id {
+        1 => String::from_slice(env, "LS"),
+        2 => String::from_slice(env, "S"),
+        3 => String::from_slice(env, "C"),
+        4 => String::from_slice(env, "ELS"),
+        5 => String::from_slice(env, "ES"),
+        6 => String::from_slice(env, "EC"),
+        _ => String::from_slice(env, "LS"),
+    };
+
+    // Mint a new NFT.
+    let nft_metadata: TokenMetadata = TokenMetadata {
+        token_uri: new_token_uri,
+        name: _name,
+        symbol: _symbol,
+    };
+    let nft_metadata_key = NFTDataKey::NFTMetadata(token_id);
+    env.storage()
+        .instance()
+        .set(&nft_metadata_key, &nft_metadata);
+
+    receive_balance(env, to.clone(), token_id, amount);
+    assign_owner(env, to.clone(), token_id, amount);
+    write_supply(env, token_id, read_supply(env, token_id) + amount);
+    env.storage()
+        .instance()
+        .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+
+    Ok(())
+}
+
+// Burns one sword of a class without an authorization check, for the battle
+// contract's own melting and the authorized `melt_blade` entry point.
+pub(crate) fn burn(env: &Env, from: Address, token_id: u32) -> Result<(), Error> {
+    // Burn an NFT.
+    let amount: i128 = 1;
+    SwordContract::check_nonnegative_amount(amount);
+    spend_balance(env, from.clone(), token_id, amount);
+    clear_owner(env, from, token_id);
+    write_supply(env, token_id, (read_supply(env, token_id) - amount).max(0));
+    env.storage()
+        .instance()
+        .bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    Ok(())
+}
@@ -1,7 +1,11 @@
 #![cfg(test)]
 
 use super::*;
-use soroban_sdk::{testutils::Address as _, Address, Env};
+use soroban_sdk::{
+    symbol_short,
+    testutils::{Address as _, Events, Ledger, MockAuth, MockAuthInvoke},
+    vec, Address, Env, IntoVal, Val,
+};
 
 // mod battle {
 //     soroban_sdk::contractimport!(
@@ -28,6 +32,43 @@ fn setup_test() -> (
     (env, contract_id, user_1, user_2, client)
 }
 
+// Pins damage variance at exactly 100% with no critical hits, so every
+// round's damage is known
+fn pin_damage_variance(env: &Env, contract_id: &Address) {
+    env.as_contract(contract_id, || rng::set_override(env, Some(20)));
+}
+
+// Registers a sword contract with a fresh admin and minter
+fn setup_sword_contract(
+    env: &Env,
+) -> (
+    sword_contract::SwordContractClient<'static>,
+    Address,
+    Address,
+) {
+    let sword_id = env.register_contract(None, SwordContract);
+    let sword = sword_contract::SwordContractClient::new(env, &sword_id);
+    let admin = Address::random(env);
+    let minter = Address::random(env);
+    sword.initialize(&admin, &minter);
+    (sword, admin, minter)
+}
+
+// Initializes the battle contract with a sword contract it mints on
+fn initialize_contract(
+    env: &Env,
+    contract_id: &Address,
+    client: &BattleContractClient,
+    admin: &Address,
+    wager_token: &Address,
+) -> sword_contract::SwordContractClient<'static> {
+    let sword_id = env.register_contract(None, SwordContract);
+    let sword = sword_contract::SwordContractClient::new(env, &sword_id);
+    sword.initialize(admin, contract_id);
+    client.initialize(admin, &sword_id, wager_token);
+    sword
+}
+
 fn setup_battle_sequence() -> (
     Env,
     Address,
@@ -58,13 +99,14 @@ fn setup_battle_sequence() -> (
     client.forge_blade(&user_1, &1);
     client.forge_blade(&user_2, &1);
     assert_eq!(
-        client.create_battle(&battle_name, &user_1),
+        client.create_battle(&battle_name, &user_1, &2),
         (Ok(()), Ok(()))
     );
     assert_eq!(
         client.join_battle(&battle_name, &user_2.clone()),
         (Ok(()), Ok(()))
     );
+    pin_damage_variance(&env, &contract_id);
     (
         env,
         contract_id,
@@ -81,7 +123,7 @@ fn setup_battle_sequence() -> (
 
 #[test]
 fn create_player() {
-    let (_env, _contract_id, user_1, _user_2, client) = setup_test();
+    let (env, _contract_id, user_1, _user_2, client) = setup_test();
     client.add_player(&user_1);
     assert_eq!(
         client.get_player_stats(&user_1),
@@ -91,15 +133,30 @@ fn create_player() {
             health: 100,
             attack: 10,
             defense: 10,
+            base_health: 100,
+            base_attack: 10,
+            base_defense: 10,
             in_battle: false,
             has_sword: false,
+            rating: STARTING_RATING,
+            peak_rating: STARTING_RATING,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+            streak: 0,
+            experience: 0,
+            level: 1,
+            first_win_at: OptionalTimestamp::None,
+            durability: 0,
+            total_damage_taken: 0,
+            current_battle: Symbol::new(&env, ""),
         }
     );
 }
 
 #[test]
 fn forge_and_melt_blade() {
-    let (_env, _contract_id, user_1, _user_2, client) = setup_test();
+    let (env, _contract_id, user_1, _user_2, client) = setup_test();
     client.add_player(&user_1);
     assert_eq!(client.get_player_stats(&user_1).sword_class, 0);
     let class_1: u32 = 1;
@@ -114,8 +171,23 @@ fn forge_and_melt_blade() {
             health: 108,
             attack: 14,
             defense: 13,
+            base_health: 100,
+            base_attack: 10,
+            base_defense: 10,
             in_battle: false,
             has_sword: true,
+            rating: STARTING_RATING,
+            peak_rating: STARTING_RATING,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+            streak: 0,
+            experience: 0,
+            level: 1,
+            first_win_at: OptionalTimestamp::None,
+            durability: 20,
+            total_damage_taken: 0,
+            current_battle: Symbol::new(&env, ""),
         }
     );
 
@@ -128,8 +200,23 @@ fn forge_and_melt_blade() {
             health: 100,
             attack: 10,
             defense: 10,
+            base_health: 100,
+            base_attack: 10,
+            base_defense: 10,
             in_battle: false,
             has_sword: false,
+            rating: STARTING_RATING,
+            peak_rating: STARTING_RATING,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+            streak: 0,
+            experience: 0,
+            level: 1,
+            first_win_at: OptionalTimestamp::None,
+            durability: 0,
+            total_damage_taken: 0,
+            current_battle: Symbol::new(&env, ""),
         }
     );
 
@@ -142,8 +229,23 @@ fn forge_and_melt_blade() {
             health: 97,
             attack: 26,
             defense: 12,
+            base_health: 100,
+            base_attack: 10,
+            base_defense: 10,
             in_battle: false,
-            has_sword: true
+            has_sword: true,
+            rating: STARTING_RATING,
+            peak_rating: STARTING_RATING,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+            streak: 0,
+            experience: 0,
+            level: 1,
+            first_win_at: OptionalTimestamp::None,
+            durability: 15,
+            total_damage_taken: 0,
+            current_battle: Symbol::new(&env, ""),
         }
     );
 }
@@ -154,14 +256,14 @@ fn create_and_join_battle() {
     let class_1: u32 = 1;
     let class_2: u32 = 2;
 
+    client.add_player(&user_1);
     client.forge_blade(&user_1, &class_1);
 
     let battle_name = Symbol::new(&env, "Constantine");
 
     // Step 1: Create the battle with user_1
-    client.add_player(&user_1);
     assert_eq!(
-        client.create_battle(&battle_name, &user_1),
+        client.create_battle(&battle_name, &user_1, &2),
         (Ok(()), Ok(()))
     );
 
@@ -172,7 +274,18 @@ fn create_and_join_battle() {
         players: map![&env, (user_1.clone(), 1), (contract_id.clone(), 2)],
         moves: map![&env, (user_1.clone(), 0), (contract_id.clone(), 0)],
         turns: 0,
-        winner: contract_id.clone(),
+        winner: OptionalAddress::None,
+        last_move_ts: 0,
+        is_draw: false,
+        result: BattleResult::Ongoing,
+        rounds: Vec::new(&env),
+        round_count: 0,
+        first_mover: OptionalAddress::None,
+        specials: map![&env],
+        invited_at: OptionalLedger::None,
+        max_players: 2,
+        seed_commit: BytesN::from_array(&env, &[0; 32]),
+        revealed_seed: 0,
     };
     assert_eq!(
         client.get_battle(&battle_name),
@@ -190,20 +303,25 @@ fn create_and_join_battle() {
     );
 
     assert!(client.get_player_stats(&user_2).in_battle);
-    let player_1 = client
-        .get_battle(&battle_name)
-        .players
-        .keys()
-        .get(0)
-        .unwrap();
 
     let expected_battle_after_join = Battle {
         battle_status: 1,
         name: battle_name.clone(),
-        players: map![&env, (player_1.clone(), 1), (user_2.clone(), 2)],
-        moves: map![&env, (player_1.clone(), 0), (user_2.clone(), 0)],
+        players: map![&env, (user_1.clone(), 1), (user_2.clone(), 2)],
+        moves: map![&env, (user_1.clone(), 0), (user_2.clone(), 0)],
         turns: 0,
-        winner: contract_id.clone(),
+        winner: OptionalAddress::None,
+        last_move_ts: 0,
+        is_draw: false,
+        result: BattleResult::Ongoing,
+        rounds: Vec::new(&env),
+        round_count: 0,
+        first_mover: OptionalAddress::None,
+        specials: map![&env],
+        invited_at: OptionalLedger::None,
+        max_players: 2,
+        seed_commit: BytesN::from_array(&env, &[0; 32]),
+        revealed_seed: 0,
     };
     assert_eq!(
         client.get_battle(&battle_name),
@@ -216,7 +334,7 @@ fn create_and_join_battle() {
 fn test_battle_sequence() {
     let (
         env,
-        contract_id,
+        _contract_id,
         user_1,
         user_2,
         _class_1,
@@ -233,7 +351,18 @@ fn test_battle_sequence() {
         players: map![&env, (user_1.clone(), 1), (user_2.clone(), 2)],
         moves: map![&env, (user_1.clone(), 0), (user_2.clone(), 0)],
         turns: 0,
-        winner: contract_id.clone(),
+        winner: OptionalAddress::None,
+        last_move_ts: 0,
+        is_draw: false,
+        result: BattleResult::Ongoing,
+        rounds: Vec::new(&env),
+        round_count: 0,
+        first_mover: OptionalAddress::None,
+        specials: map![&env],
+        invited_at: OptionalLedger::None,
+        max_players: 2,
+        seed_commit: BytesN::from_array(&env, &[0; 32]),
+        revealed_seed: 0,
     };
     assert_eq!(
         client.get_battle(&battle_name),
@@ -256,8 +385,8 @@ fn test_battle_sequence() {
     let player_1 = client.get_player_stats(&user_1);
     let player_2 = client.get_player_stats(&user_2);
 
-    assert_eq!(player_1.health, 94);
-    assert_eq!(player_2.health, 94);
+    assert_eq!(player_1.health, 100);
+    assert_eq!(player_2.health, 100);
 
     // // Step 4: User 2 attacks
     // assert_eq!(
@@ -378,3 +507,3618 @@ fn test_battle_sequence() {
 //         }
 //     );
 // }
+
+#[test]
+fn end_battle_releases_both_players() {
+    let (
+        env,
+        _contract_id,
+        user_1,
+        user_2,
+        _class_1,
+        _class_2,
+        _attack,
+        _defend,
+        battle_name,
+        client,
+    ) = setup_battle_sequence();
+
+    // Both players attack until the battle is decided
+    while client.get_battle(&battle_name).battle_status == 1 {
+        client.attack_or_defend_choice(&user_1, &1, &battle_name);
+        client.attack_or_defend_choice(&user_2, &1, &battle_name);
+    }
+
+    // Identical longswords fall in the same round
+    let battle = client.get_battle(&battle_name);
+    assert_eq!(battle.battle_status, 2);
+    assert!(battle.is_draw);
+
+    assert!(!client.get_player_stats(&user_1).in_battle);
+    assert!(!client.get_player_stats(&user_2).in_battle);
+
+    // Both players are free to start a new battle
+    assert_eq!(
+        client.create_battle(&Symbol::new(&env, "Aurelian"), &user_1, &2),
+        (Ok(()), Ok(()))
+    );
+    assert_eq!(
+        client.create_battle(&Symbol::new(&env, "Diocletian"), &user_2, &2),
+        (Ok(()), Ok(()))
+    );
+}
+
+#[test]
+fn forfeit_mid_battle() {
+    let (
+        _env,
+        _contract_id,
+        user_1,
+        user_2,
+        _class_1,
+        _class_2,
+        _attack,
+        _defend,
+        battle_name,
+        client,
+    ) = setup_battle_sequence();
+
+    client.attack_or_defend_choice(&user_1, &1, &battle_name);
+    client.forfeit_battle(&user_2, &battle_name);
+
+    let battle = client.get_battle(&battle_name);
+    assert_eq!(battle.battle_status, 2);
+    assert_eq!(battle.winner, OptionalAddress::Some(user_1.clone()));
+    assert!(!client.get_player_stats(&user_1).in_battle);
+    assert!(!client.get_player_stats(&user_2).in_battle);
+
+    // An ended battle can't be forfeited again
+    assert!(client.try_forfeit_battle(&user_2, &battle_name).is_err());
+}
+
+#[test]
+fn forfeit_in_free_for_all_only_removes_the_forfeiter() {
+    let (env, _contract_id, _user_1, _user_2, client) = setup_test();
+    let name = Symbol::new(&env, "Marengo");
+    let users = [
+        Address::random(&env),
+        Address::random(&env),
+        Address::random(&env),
+    ];
+    for user in users.iter() {
+        client.add_player(user);
+    }
+    assert_eq!(client.create_battle(&name, &users[0], &3), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &users[1]), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &users[2]), (Ok(()), Ok(())));
+
+    // The others have moved, so the round resolves once the third player leaves
+    client.attack_or_defend_choice(&users[0], &2, &name);
+    client.attack_or_defend_choice(&users[1], &2, &name);
+    client.forfeit_battle(&users[2], &name);
+
+    let battle = client.get_battle(&name);
+    assert_eq!(battle.battle_status, 1);
+    assert_eq!(battle.players.len(), 2);
+    assert!(!battle.players.contains_key(users[2].clone()));
+    assert_eq!(client.get_battle_history(&name).len(), 1);
+    let forfeiter = client.get_player_stats(&users[2]);
+    assert!(!forfeiter.in_battle);
+    assert_eq!(forfeiter.losses, 1);
+    for user in users[..2].iter() {
+        let stats = client.get_player_stats(user);
+        assert!(stats.in_battle);
+        assert_eq!((stats.wins, stats.losses), (0, 0));
+    }
+
+    // With two left, a forfeit ends the battle as usual
+    client.forfeit_battle(&users[1], &name);
+    let battle = client.get_battle(&name);
+    assert_eq!(battle.battle_status, 2);
+    assert_eq!(battle.winner, OptionalAddress::Some(users[0].clone()));
+    assert_eq!(client.get_player_stats(&users[0]).wins, 1);
+    assert_eq!(client.get_player_stats(&users[1]).losses, 1);
+}
+
+#[test]
+fn forfeit_rejects_non_participant() {
+    let (
+        env,
+        _contract_id,
+        _user_1,
+        _user_2,
+        _class_1,
+        _class_2,
+        _attack,
+        _defend,
+        battle_name,
+        client,
+    ) = setup_battle_sequence();
+
+    let outsider = Address::random(&env);
+    client.add_player(&outsider);
+    assert!(client.try_forfeit_battle(&outsider, &battle_name).is_err());
+    assert_eq!(client.get_battle(&battle_name).battle_status, 1);
+}
+
+// Creates a battle between two players and has both attack until it ends
+fn fight_to_the_end(
+    client: &BattleContractClient<'static>,
+    battle_name: &Symbol,
+    user_1: &Address,
+    user_2: &Address,
+) {
+    assert_eq!(
+        client.create_battle(battle_name, user_1, &2),
+        (Ok(()), Ok(()))
+    );
+    assert_eq!(client.join_battle(battle_name, user_2), (Ok(()), Ok(())));
+    fight_to_the_end_started(client, battle_name, user_1, user_2);
+}
+
+// Both players attack every round until an already started battle is over
+fn fight_to_the_end_started(
+    client: &BattleContractClient<'static>,
+    battle_name: &Symbol,
+    user_1: &Address,
+    user_2: &Address,
+) {
+    while client.get_battle(battle_name).battle_status == 1 {
+        client.attack_or_defend_choice(user_1, &1, battle_name);
+        client.attack_or_defend_choice(user_2, &1, battle_name);
+    }
+}
+
+#[test]
+fn rating_leaderboard_orders_and_truncates() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    let user_3 = Address::random(&env);
+    let user_4 = Address::random(&env);
+    for user in [&user_1, &user_2, &user_3, &user_4] {
+        client.add_player(user);
+    }
+
+    // A Sabre always outlasts a Longsword when both keep attacking
+    client.forge_blade(&user_1, &2);
+    client.forge_blade(&user_2, &1);
+    client.forge_blade(&user_3, &1);
+
+    fight_to_the_end(&client, &Symbol::new(&env, "Actium"), &user_1, &user_2);
+    fight_to_the_end(&client, &Symbol::new(&env, "Pharsalus"), &user_1, &user_3);
+    assert_eq!(
+        client.get_battle(&Symbol::new(&env, "Actium")).winner,
+        OptionalAddress::Some(user_1.clone())
+    );
+    assert_eq!(
+        client.get_battle(&Symbol::new(&env, "Pharsalus")).winner,
+        OptionalAddress::Some(user_1.clone())
+    );
+
+    assert_eq!(
+        client.rating_leaderboard(&3),
+        vec![
+            &env,
+            (user_1.clone(), 1232),
+            (user_4.clone(), STARTING_RATING),
+            (user_2.clone(), 1184),
+        ]
+    );
+    assert_eq!(client.rating_leaderboard(&10).len(), 4);
+    assert_eq!(client.rating_leaderboard(&0).len(), 0);
+}
+
+#[test]
+fn tick_advances_bot_battles() {
+    let (env, contract_id, _user_1, _user_2, client) = setup_test();
+    let names = [
+        Symbol::new(&env, "Cannae"),
+        Symbol::new(&env, "Zama"),
+        Symbol::new(&env, "Trebia"),
+    ];
+    for name in names.iter() {
+        let user = Address::random(&env);
+        client.add_player(&user);
+        client.forge_blade(&user, &1);
+        client.create_auto_battle(name, &user);
+        client.attack_or_defend_choice(&user, &1, name);
+    }
+
+    // Nothing to do for a battle where the player hasn't moved yet
+    let idle_user = Address::random(&env);
+    client.add_player(&idle_user);
+    client.create_auto_battle(&Symbol::new(&env, "Lake"), &idle_user);
+
+    assert_eq!(client.tick(&2), 2);
+    for name in names[..2].iter() {
+        let battle = client.get_battle(name);
+        assert_eq!(battle.turns, 0);
+        assert_eq!(battle.moves.get(contract_id.clone()), Some(0));
+    }
+    assert_eq!(client.get_battle(&names[2]).turns, 1);
+
+    assert_eq!(client.tick(&2), 1);
+    assert_eq!(client.get_battle(&names[2]).turns, 0);
+    assert_eq!(client.tick(&2), 0);
+}
+
+#[test]
+fn claim_timeout_win_after_deadline() {
+    let (
+        env,
+        _contract_id,
+        user_1,
+        user_2,
+        _class_1,
+        _class_2,
+        _attack,
+        _defend,
+        battle_name,
+        client,
+    ) = setup_battle_sequence();
+
+    env.ledger().with_mut(|li| li.timestamp = 1_000);
+    client.attack_or_defend_choice(&user_1, &1, &battle_name);
+    assert_eq!(client.get_battle(&battle_name).last_move_ts, 1_000);
+
+    // The waiting player can't claim before the deadline, and the stalling one never can
+    env.ledger()
+        .with_mut(|li| li.timestamp = 1_000 + MOVE_TIMEOUT);
+    assert!(client.try_claim_timeout_win(&user_1, &battle_name).is_err());
+
+    env.ledger()
+        .with_mut(|li| li.timestamp = 1_001 + MOVE_TIMEOUT);
+    assert!(client.try_claim_timeout_win(&user_2, &battle_name).is_err());
+    client.claim_timeout_win(&user_1, &battle_name);
+
+    let battle = client.get_battle(&battle_name);
+    assert_eq!(battle.battle_status, 2);
+    assert_eq!(battle.winner, OptionalAddress::Some(user_1.clone()));
+    assert!(!client.get_player_stats(&user_1).in_battle);
+    assert!(!client.get_player_stats(&user_2).in_battle);
+}
+
+#[test]
+fn bot_move_cannot_be_submitted_directly() {
+    let (env, contract_id, user_1, _user_2, client) = setup_test();
+    let battle_name = Symbol::new(&env, "Alesia");
+    client.add_player(&user_1);
+    client.create_auto_battle(&battle_name, &user_1);
+
+    assert!(client
+        .try_attack_or_defend_choice(&contract_id, &1, &battle_name)
+        .is_err());
+    assert_eq!(
+        client
+            .get_battle(&battle_name)
+            .moves
+            .get(contract_id.clone()),
+        Some(0)
+    );
+}
+
+// Returns the most recent contract event, as a single-element Vec for comparison
+fn last_event(env: &Env) -> Vec<(Address, Vec<Val>, Val)> {
+    let events = env.events().all();
+    events.slice(events.len() - 1..)
+}
+
+#[test]
+fn battle_lifecycle_events() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    let battle_name = Symbol::new(&env, "Hastings");
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+
+    assert_eq!(
+        client.create_battle(&battle_name, &user_1, &2),
+        (Ok(()), Ok(()))
+    );
+    assert_eq!(
+        last_event(&env),
+        vec![
+            &env,
+            (
+                contract_id.clone(),
+                (symbol_short!("battle"), symbol_short!("created")).into_val(&env),
+                (battle_name.clone(), user_1.clone()).into_val(&env),
+            ),
+        ]
+    );
+
+    assert_eq!(client.join_battle(&battle_name, &user_2), (Ok(()), Ok(())));
+    assert_eq!(
+        last_event(&env),
+        vec![
+            &env,
+            (
+                contract_id.clone(),
+                (symbol_short!("battle"), symbol_short!("joined")).into_val(&env),
+                (battle_name.clone(), user_2.clone()).into_val(&env),
+            ),
+        ]
+    );
+
+    client.attack_or_defend_choice(&user_1, &1, &battle_name);
+    assert_eq!(
+        last_event(&env),
+        vec![
+            &env,
+            (
+                contract_id.clone(),
+                (symbol_short!("battle"), symbol_short!("moved")).into_val(&env),
+                (battle_name.clone(), user_1.clone()).into_val(&env),
+            ),
+        ]
+    );
+
+    client.forfeit_battle(&user_2, &battle_name);
+    assert_eq!(
+        last_event(&env),
+        vec![
+            &env,
+            (
+                contract_id.clone(),
+                (symbol_short!("battle"), symbol_short!("ended")).into_val(&env),
+                (battle_name.clone(), user_1.clone()).into_val(&env),
+            ),
+        ]
+    );
+}
+
+#[test]
+fn rng_override_is_deterministic() {
+    let (env, contract_id, _user_1, _user_2, _client) = setup_test();
+    env.as_contract(&contract_id, || {
+        let value = rng::next_u64(&env, 1, 1);
+        assert_eq!(value, rng::next_u64(&env, 1, 1));
+        assert_ne!(value, rng::next_u64(&env, 1, 2));
+        assert_ne!(value, rng::next_u64(&env, 2, 1));
+
+        rng::set_override(&env, Some(42));
+        assert_eq!(rng::next_u64(&env, 1, 1), 42);
+        assert_eq!(rng::next_u64(&env, 7, 3), 42);
+
+        rng::set_override(&env, None);
+        assert_eq!(rng::next_u64(&env, 1, 1), value);
+    });
+}
+
+#[test]
+fn bot_move_follows_rng() {
+    let (env, contract_id, user_1, _user_2, client) = setup_test();
+    let battle_name = Symbol::new(&env, "Marathon");
+    client.add_player(&user_1);
+    client.create_auto_battle(&battle_name, &user_1);
+
+    // Start wounded so there is room to heal
+    let mut stats = client.get_player_stats(&user_1);
+    stats.health = 90;
+    env.as_contract(&contract_id, || {
+        BattleContract::set_player_stats(env.clone(), user_1.clone(), stats).unwrap()
+    });
+
+    // The draw picks from the valid moves in order: a draw of 1 makes the
+    // bot defend, so both players heal
+    env.as_contract(&contract_id, || rng::set_override(&env, Some(1)));
+    client.attack_or_defend_choice(&user_1, &2, &battle_name);
+    assert_eq!(client.tick(&1), 1);
+    assert_eq!(client.get_player_stats(&user_1).health, 92);
+
+    // A draw of 3 wraps around to a light attack
+    env.as_contract(&contract_id, || rng::set_override(&env, Some(3)));
+    client.attack_or_defend_choice(&user_1, &2, &battle_name);
+    assert_eq!(client.tick(&1), 1);
+    let rounds = client.get_battle(&battle_name).rounds;
+    assert_eq!(rounds.len(), 2);
+    assert_eq!(
+        rounds.get(1).unwrap().moves.get(contract_id.clone()),
+        Some(1)
+    );
+
+    // And the bot can go for a heavy attack too
+    env.as_contract(&contract_id, || {
+        rng::set_override(&env, Some(5));
+        assert_eq!(BattleContract::bot_move(&env, 0), 3);
+    });
+}
+
+#[test]
+fn forge_and_melt_events() {
+    let (env, contract_id, user_1, _user_2, client) = setup_test();
+    client.add_player(&user_1);
+
+    client.forge_blade(&user_1, &2);
+    assert_eq!(
+        last_event(&env),
+        vec![
+            &env,
+            (
+                contract_id.clone(),
+                (symbol_short!("sword"), symbol_short!("forged")).into_val(&env),
+                (user_1.clone(), 2_u32).into_val(&env),
+            ),
+        ]
+    );
+
+    client.melt_blade(&user_1, &2);
+    assert_eq!(
+        last_event(&env),
+        vec![
+            &env,
+            (
+                contract_id.clone(),
+                (symbol_short!("sword"), symbol_short!("melted")).into_val(&env),
+                (user_1.clone(), 2_u32).into_val(&env),
+            ),
+        ]
+    );
+}
+
+#[test]
+fn leaderboard_sorted_by_wins_then_losses() {
+    let (env, contract_id, _user_1, _user_2, client) = setup_test();
+    pin_damage_variance(&env, &contract_id);
+    let sabre_1 = Address::random(&env);
+    let sabre_2 = Address::random(&env);
+    let sabre_3 = Address::random(&env);
+    let longsword = Address::random(&env);
+    for user in [&longsword, &sabre_1, &sabre_2, &sabre_3] {
+        client.add_player(user);
+    }
+    for user in [&sabre_1, &sabre_2, &sabre_3] {
+        client.forge_blade(user, &2);
+    }
+    client.forge_blade(&longsword, &1);
+
+    fight_to_the_end(&client, &Symbol::new(&env, "Issus"), &sabre_1, &longsword);
+    fight_to_the_end(
+        &client,
+        &Symbol::new(&env, "Granicus"),
+        &sabre_2,
+        &longsword,
+    );
+    fight_to_the_end(
+        &client,
+        &Symbol::new(&env, "Hydaspes"),
+        &sabre_3,
+        &longsword,
+    );
+
+    // Between the two 1-0 sabres, one ends up 2-0 and the other 1-1. A
+    // mirror match where both keep attacking is a draw, so sabre_2 defends
+    // the first two rounds and falls behind.
+    let final_battle = Symbol::new(&env, "Gaugamela");
+    assert_eq!(
+        client.create_battle(&final_battle, &sabre_1, &2),
+        (Ok(()), Ok(()))
+    );
+    assert_eq!(
+        client.join_battle(&final_battle, &sabre_2),
+        (Ok(()), Ok(()))
+    );
+    for _ in 0..2 {
+        client.attack_or_defend_choice(&sabre_1, &1, &final_battle);
+        client.attack_or_defend_choice(&sabre_2, &2, &final_battle);
+    }
+    while client.get_battle(&final_battle).battle_status == 1 {
+        client.attack_or_defend_choice(&sabre_1, &1, &final_battle);
+        client.attack_or_defend_choice(&sabre_2, &1, &final_battle);
+    }
+    let champion = sabre_1.clone();
+    let runner_up = sabre_2.clone();
+    assert_eq!(
+        client.get_battle(&final_battle).winner,
+        OptionalAddress::Some(champion.clone())
+    );
+
+    let leaderboard = client.get_leaderboard(&3);
+    assert_eq!(leaderboard.len(), 3);
+    let top = leaderboard.get(0).unwrap();
+    assert_eq!((top.player_address, top.wins, top.losses), (champion, 2, 0));
+    let second = leaderboard.get(1).unwrap();
+    assert_eq!(
+        (second.player_address, second.wins, second.losses),
+        (sabre_3.clone(), 1, 0)
+    );
+    let third = leaderboard.get(2).unwrap();
+    assert_eq!(
+        (third.player_address, third.wins, third.losses),
+        (runner_up, 1, 1)
+    );
+
+    let last = client.get_leaderboard(&10).get(3).unwrap();
+    assert_eq!(
+        (last.player_address, last.wins, last.losses),
+        (longsword, 0, 3)
+    );
+}
+
+#[test]
+fn peak_rating_never_decreases() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+
+    client.forge_blade(&user_1, &2);
+    client.forge_blade(&user_2, &1);
+    fight_to_the_end(&client, &Symbol::new(&env, "Lepanto"), &user_1, &user_2);
+    assert_eq!(client.get_player_stats(&user_1).rating, 1216);
+    assert_eq!(client.get_peak_rating(&user_1), 1216);
+    assert_eq!(client.get_peak_rating(&user_2), STARTING_RATING);
+
+    // Swap swords so the rematch goes the other way
+    client.melt_blade(&user_1, &2);
+    client.melt_blade(&user_2, &1);
+    client.forge_blade(&user_1, &1);
+    client.forge_blade(&user_2, &2);
+    fight_to_the_end(&client, &Symbol::new(&env, "Preveza"), &user_2, &user_1);
+    assert_eq!(client.get_player_stats(&user_1).rating, 1199);
+    assert_eq!(client.get_peak_rating(&user_1), 1216);
+    assert_eq!(client.get_player_stats(&user_2).rating, 1201);
+    assert_eq!(client.get_peak_rating(&user_2), 1201);
+}
+
+#[test]
+fn resolve_battle_after_timeout() {
+    let (
+        env,
+        _contract_id,
+        user_1,
+        user_2,
+        _class_1,
+        _class_2,
+        attack,
+        _defend,
+        battle_name,
+        client,
+    ) = setup_battle_sequence();
+    client.attack_or_defend_choice(&user_1, &attack.unwrap(), &battle_name);
+
+    // Can't resolve while the opponent still has time to move
+    assert!(client.try_resolve_battle(&battle_name).is_err());
+
+    env.ledger().with_mut(|li| li.timestamp = MOVE_TIMEOUT + 1);
+    client.resolve_battle(&battle_name);
+
+    // The idle player defended against the attack
+    let battle = client.get_battle(&battle_name);
+    assert_eq!(battle.turns, 0);
+    assert_eq!(battle.moves.get(user_1.clone()), Some(0));
+    assert_eq!(client.get_player_stats(&user_1).health, 108);
+    assert_eq!(client.get_player_stats(&user_2).health, 107);
+}
+
+#[test]
+fn admin_configured_move_timeout_is_honoured() {
+    let (
+        env,
+        contract_id,
+        user_1,
+        user_2,
+        _class_1,
+        _class_2,
+        attack,
+        _defend,
+        battle_name,
+        client,
+    ) = setup_battle_sequence();
+    assert_eq!(client.get_move_timeout(), MOVE_TIMEOUT);
+    let admin = Address::random(&env);
+    assert!(client.try_set_move_timeout(&admin, &60).is_err());
+    initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    assert!(client
+        .try_set_move_timeout(&Address::random(&env), &60)
+        .is_err());
+    assert!(client.try_set_move_timeout(&admin, &0).is_err());
+    client.set_move_timeout(&admin, &60);
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(client.get_move_timeout(), 60);
+
+    client.attack_or_defend_choice(&user_1, &attack.unwrap(), &battle_name);
+    env.ledger().with_mut(|li| li.timestamp = 60);
+    assert_eq!(client.timeout_winner(&battle_name), None);
+    assert!(client.try_claim_timeout_win(&user_1, &battle_name).is_err());
+
+    // Long before the default timeout, the shorter one has run out
+    env.ledger().with_mut(|li| li.timestamp = 61);
+    assert_eq!(client.timeout_winner(&battle_name), Some(user_1.clone()));
+    client.claim_timeout_win(&user_1, &battle_name);
+    assert_eq!(
+        client.get_battle(&battle_name).winner,
+        OptionalAddress::Some(user_1)
+    );
+    assert!(!client.get_player_stats(&user_2).in_battle);
+}
+
+#[test]
+fn resolve_battle_against_bot() {
+    let (env, contract_id, user_1, _user_2, client) = setup_test();
+    let name = Symbol::new(&env, "Alesia");
+    client.add_player(&user_1);
+    client.forge_blade(&user_1, &1);
+    client.challenge_bot(&user_1, &name);
+    client.attack_or_defend_choice(&user_1, &1, &name);
+    assert_eq!(client.get_battle(&name).turns, 1);
+
+    env.as_contract(&contract_id, || rng::set_override(&env, Some(1)));
+    client.resolve_battle(&name);
+
+    let battle = client.get_battle(&name);
+    assert_eq!(battle.battle_status, BattleStatus::Ended as u64);
+    assert_eq!(battle.winner, OptionalAddress::Some(user_1.clone()));
+    assert_eq!(battle.turns, 0);
+}
+
+#[test]
+fn simultaneous_knockout_is_a_draw() {
+    let (
+        env,
+        contract_id,
+        user_1,
+        user_2,
+        _class_1,
+        _class_2,
+        attack,
+        _defend,
+        battle_name,
+        client,
+    ) = setup_battle_sequence();
+
+    // Glass cannons: each hits harder than the other can take
+    for user in [&user_1, &user_2] {
+        let mut stats = client.get_player_stats(user);
+        stats.health = 10;
+        stats.attack = 50;
+        env.as_contract(&contract_id, || {
+            BattleContract::set_player_stats(env.clone(), user.clone(), stats).unwrap()
+        });
+    }
+    client.attack_or_defend_choice(&user_1, &attack.unwrap(), &battle_name);
+    client.attack_or_defend_choice(&user_2, &attack.unwrap(), &battle_name);
+
+    let battle = client.get_battle(&battle_name);
+    assert_eq!(battle.battle_status, BattleStatus::Ended as u64);
+    assert!(battle.is_draw);
+    assert_eq!(battle.winner, OptionalAddress::None);
+    assert_eq!(client.get_winner(&battle_name), None);
+    for user in [&user_1, &user_2] {
+        let stats = client.get_player_stats(user);
+        assert!(!stats.in_battle);
+        // Longsword holders are back at full health, bonus included
+        assert_eq!(stats.health, 108);
+        assert_eq!((stats.wins, stats.losses), (0, 0));
+        assert_eq!(stats.rating, STARTING_RATING);
+    }
+}
+
+#[test]
+fn streak_tracks_consecutive_results() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &2);
+    client.forge_blade(&user_2, &1);
+
+    fight_to_the_end(&client, &Symbol::new(&env, "Marathon"), &user_1, &user_2);
+    assert_eq!(client.get_streak(&user_1), 1);
+    assert_eq!(client.get_streak(&user_2), -1);
+
+    fight_to_the_end(&client, &Symbol::new(&env, "Plataea"), &user_1, &user_2);
+    assert_eq!(client.get_streak(&user_1), 2);
+    assert_eq!(client.get_streak(&user_2), -2);
+
+    client.melt_blade(&user_1, &2);
+    client.melt_blade(&user_2, &1);
+    client.forge_blade(&user_1, &1);
+    client.forge_blade(&user_2, &2);
+    fight_to_the_end(&client, &Symbol::new(&env, "Mycale"), &user_1, &user_2);
+    assert_eq!(client.get_streak(&user_1), -1);
+    assert_eq!(client.get_streak(&user_2), 1);
+}
+
+#[test]
+fn challenge_bot_requires_player_auth() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    let name = Symbol::new(&env, "Thermopylae");
+
+    // user_2 can't drag user_1 into a bot battle
+    env.mock_auths(&[MockAuth {
+        address: &user_2,
+        invoke: &MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "challenge_bot",
+            args: (user_1.clone(), name.clone()).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    assert!(client.try_challenge_bot(&user_1, &name).is_err());
+    assert!(!client.get_player_stats(&user_1).in_battle);
+
+    env.mock_auths(&[MockAuth {
+        address: &user_1,
+        invoke: &MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "challenge_bot",
+            args: (user_1.clone(), name.clone()).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    client.challenge_bot(&user_1, &name);
+    assert!(client.get_player_stats(&user_1).in_battle);
+    assert_eq!(client.get_battle(&name).battle_status, 1);
+}
+
+#[test]
+fn healing_is_capped_by_sword_class() {
+    let (
+        _env,
+        _contract_id,
+        user_1,
+        user_2,
+        _class_1,
+        _class_2,
+        attack,
+        defend,
+        battle_name,
+        client,
+    ) = setup_battle_sequence();
+
+    // user_2 takes a hit, then both players heal repeatedly
+    client.attack_or_defend_choice(&user_1, &attack.unwrap(), &battle_name);
+    client.attack_or_defend_choice(&user_2, &defend.unwrap(), &battle_name);
+    assert_eq!(client.get_player_stats(&user_2).health, 107);
+
+    for _ in 0..5 {
+        client.attack_or_defend_choice(&user_1, &defend.unwrap(), &battle_name);
+        client.attack_or_defend_choice(&user_2, &defend.unwrap(), &battle_name);
+        assert!(client.get_player_stats(&user_1).health <= 108);
+        assert!(client.get_player_stats(&user_2).health <= 108);
+    }
+    assert_eq!(client.get_player_stats(&user_1).health, 108);
+    assert_eq!(client.get_player_stats(&user_2).health, 108);
+}
+
+#[test]
+fn bot_and_pvp_wins_pay_configured_rewards() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    let admin = Address::random(&env);
+    initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    client.set_rewards(&admin, &25, &5);
+    assert_eq!(
+        client.get_rewards(),
+        RewardConfig {
+            pvp_win: 25,
+            bot_win: 5
+        }
+    );
+
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &2);
+    client.forge_blade(&user_2, &1);
+    fight_to_the_end(&client, &Symbol::new(&env, "Hastings"), &user_1, &user_2);
+    assert_eq!(client.get_player_stats(&user_1).experience, 25);
+
+    // The bot has no stats, so a single attack wins
+    let name = Symbol::new(&env, "Stamford");
+    client.challenge_bot(&user_2, &name);
+    client.attack_or_defend_choice(&user_2, &1, &name);
+    env.as_contract(&contract_id, || rng::set_override(&env, Some(2)));
+    client.resolve_battle(&name);
+    assert_eq!(
+        client.get_battle(&name).winner,
+        OptionalAddress::Some(user_2.clone())
+    );
+    // user_2 also earned experience for losing to user_1
+    assert_eq!(
+        client.get_player_stats(&user_2).experience,
+        5 + LOSS_EXPERIENCE
+    );
+    // The bot never gets a player record of its own
+    let bot_has_record = env.as_contract(&contract_id, || {
+        env.storage()
+            .instance()
+            .has(&DataKey::Player(contract_id.clone()))
+    });
+    assert!(!bot_has_record);
+}
+
+#[test]
+fn levels_survive_melting_a_blade() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    let admin = Address::random(&env);
+    initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    client.set_rewards(&admin, &EXPERIENCE_PER_LEVEL, &0);
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &2);
+    client.forge_blade(&user_2, &1);
+    pin_damage_variance(&env, &contract_id);
+
+    fight_to_the_end(&client, &Symbol::new(&env, "Hastings"), &user_1, &user_2);
+    fight_to_the_end(&client, &Symbol::new(&env, "Bouvines"), &user_1, &user_2);
+    let stats = client.get_player_stats(&user_1);
+    assert_eq!(stats.wins, 2);
+    assert_eq!(
+        (stats.experience, stats.level),
+        (2 * EXPERIENCE_PER_LEVEL, 3)
+    );
+    assert_eq!(stats.attack, 26 + 2 * LEVEL_ATTACK_BONUS);
+    assert_eq!(stats.defense, 12 + 2 * LEVEL_DEFENSE_BONUS);
+
+    // The loser gains a little experience, but not enough to level up
+    let stats = client.get_player_stats(&user_2);
+    assert_eq!((stats.experience, stats.level), (2 * LOSS_EXPERIENCE, 1));
+    assert_eq!((stats.attack, stats.defense), (14, 13));
+
+    // Melting the sword only takes away what the sword gave
+    client.melt_blade(&user_1, &2);
+    let stats = client.get_player_stats(&user_1);
+    assert_eq!(stats.attack, 10 + 2 * LEVEL_ATTACK_BONUS);
+    assert_eq!(stats.defense, 10 + 2 * LEVEL_DEFENSE_BONUS);
+    assert_eq!(stats.level, 3);
+}
+
+#[test]
+fn set_rewards_requires_admin() {
+    let (env, contract_id, _user_1, _user_2, client) = setup_test();
+    let admin = Address::random(&env);
+    assert!(client.try_set_rewards(&admin, &25, &5).is_err());
+    assert_eq!(client.get_rewards().pvp_win, DEFAULT_PVP_WIN_REWARD);
+
+    initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    assert!(client
+        .try_initialize(&admin, &Address::random(&env), &Address::random(&env))
+        .is_err());
+    // Nobody else can stand in for the admin
+    assert!(client
+        .try_set_rewards(&Address::random(&env), &25, &5)
+        .is_err());
+    client.set_rewards(&admin, &25, &5);
+    assert_eq!(env.auths()[0].0, admin,);
+}
+
+#[test]
+fn match_preview_compares_players() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &2);
+    client.forge_blade(&user_2, &1);
+
+    let name = Symbol::new(&env, "Agincourt");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+
+    let preview = client.match_preview(&name);
+    assert_eq!(preview.player_a, user_1);
+    assert_eq!(preview.player_b, user_2);
+    assert_eq!(preview.a_effective, client.get_player_stats(&user_1));
+    assert_eq!(preview.b_effective, client.get_player_stats(&user_2));
+    // The sabre needs 5 rounds to the longsword's 7
+    assert_eq!(preview.a_win_pct, 58);
+
+    // The same matchup with the longsword creating the battle
+    let user_3 = Address::random(&env);
+    let user_4 = Address::random(&env);
+    client.add_player(&user_3);
+    client.add_player(&user_4);
+    client.forge_blade(&user_3, &1);
+    client.forge_blade(&user_4, &2);
+    let reversed_name = Symbol::new(&env, "Crecy");
+    assert_eq!(
+        client.create_battle(&reversed_name, &user_3, &2),
+        (Ok(()), Ok(()))
+    );
+    assert_eq!(
+        client.join_battle(&reversed_name, &user_4),
+        (Ok(()), Ok(()))
+    );
+    let reversed = client.match_preview(&reversed_name);
+    assert_eq!(reversed.player_a, user_3);
+    assert_eq!(preview.a_win_pct + reversed.a_win_pct, 100);
+}
+
+#[test]
+fn battle_history_records_each_round() {
+    let (
+        env,
+        _contract_id,
+        user_1,
+        user_2,
+        _class_1,
+        _class_2,
+        attack,
+        defend,
+        battle_name,
+        client,
+    ) = setup_battle_sequence();
+
+    client.attack_or_defend_choice(&user_1, &attack.unwrap(), &battle_name);
+    client.attack_or_defend_choice(&user_2, &defend.unwrap(), &battle_name);
+    client.attack_or_defend_choice(&user_1, &defend.unwrap(), &battle_name);
+    client.attack_or_defend_choice(&user_2, &defend.unwrap(), &battle_name);
+
+    let history = client.get_battle_history(&battle_name);
+    assert_eq!(history.len(), 2);
+    let first = history.get(0).unwrap();
+    assert_eq!(
+        first.moves,
+        map![&env, (user_1.clone(), 1), (user_2.clone(), 2)]
+    );
+    assert_eq!(
+        first.health,
+        map![&env, (user_1.clone(), 108), (user_2.clone(), 107)]
+    );
+    let second = history.get(1).unwrap();
+    assert_eq!(
+        second.moves,
+        map![&env, (user_1.clone(), 2), (user_2.clone(), 2)]
+    );
+    assert_eq!(
+        second.health,
+        map![&env, (user_1.clone(), 108), (user_2.clone(), 108)]
+    );
+}
+
+fn assert_both_free(client: &BattleContractClient<'static>, user_1: &Address, user_2: &Address) {
+    for user in [user_1, user_2] {
+        let stats = client.get_player_stats(user);
+        assert!(!stats.in_battle);
+        let health_bonus = if stats.has_sword {
+            client.get_sword_bonus(&stats.sword_class).0
+        } else {
+            0
+        };
+        assert_eq!(stats.health, 100u32.saturating_add_signed(health_bonus));
+    }
+}
+
+#[test]
+fn every_ending_frees_both_players() {
+    // Win
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &2);
+    client.forge_blade(&user_2, &1);
+    fight_to_the_end(&client, &Symbol::new(&env, "Tours"), &user_1, &user_2);
+    assert_eq!(
+        client.get_battle(&Symbol::new(&env, "Tours")).winner,
+        OptionalAddress::Some(user_1.clone())
+    );
+    assert_both_free(&client, &user_1, &user_2);
+
+    // Draw
+    let (
+        _env,
+        _contract_id,
+        user_1,
+        user_2,
+        _class_1,
+        _class_2,
+        _attack,
+        _defend,
+        battle_name,
+        client,
+    ) = setup_battle_sequence();
+    fight_to_the_end_started(&client, &battle_name, &user_1, &user_2);
+    assert!(client.get_battle(&battle_name).is_draw);
+    assert_both_free(&client, &user_1, &user_2);
+
+    // Forfeit
+    let (
+        _env,
+        _contract_id,
+        user_1,
+        user_2,
+        _class_1,
+        _class_2,
+        _attack,
+        _defend,
+        battle_name,
+        client,
+    ) = setup_battle_sequence();
+    client.forfeit_battle(&user_2, &battle_name);
+    assert_both_free(&client, &user_1, &user_2);
+
+    // Timeout
+    let (
+        env,
+        _contract_id,
+        user_1,
+        user_2,
+        _class_1,
+        _class_2,
+        attack,
+        _defend,
+        battle_name,
+        client,
+    ) = setup_battle_sequence();
+    client.attack_or_defend_choice(&user_1, &attack.unwrap(), &battle_name);
+    env.ledger().with_mut(|li| li.timestamp = MOVE_TIMEOUT + 1);
+    client.claim_timeout_win(&user_1, &battle_name);
+    assert_both_free(&client, &user_1, &user_2);
+}
+
+#[test]
+fn class_supply_counts_mints_per_class() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let (sword, _admin, minter) = setup_sword_contract(&env);
+    let user_1 = Address::random(&env);
+    let user_2 = Address::random(&env);
+
+    sword.mint_nft(&minter, &user_1, &1, &1);
+    sword.mint_nft(&minter, &user_2, &1, &1);
+    sword.mint_nft(&minter, &user_2, &2, &1);
+
+    assert_eq!(
+        sword.class_supply(),
+        vec![&env, (1, 2), (2, 1), (3, 0), (4, 0), (5, 0), (6, 0)]
+    );
+}
+
+#[test]
+fn owner_of_tracks_mints_and_melts() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let (sword, _admin, minter) = setup_sword_contract(&env);
+    let user_1 = Address::random(&env);
+    let user_2 = Address::random(&env);
+
+    // Token ids are assigned per mint, starting at 1
+    sword.mint_nft(&minter, &user_1, &1, &1);
+    sword.mint_nft(&minter, &user_2, &2, &1);
+    sword.mint_nft(&minter, &user_1, &2, &1);
+    assert_eq!(sword.owner_of(&1), user_1);
+    assert_eq!(sword.owner_of(&2), user_2);
+    assert_eq!(sword.owner_of(&3), user_1);
+
+    // Melting only clears the owner's tokens of that class
+    sword.melt_blade(&minter, &user_1, &2);
+    assert!(sword.try_owner_of(&3).is_err());
+    assert_eq!(sword.owner_of(&1), user_1);
+    assert_eq!(sword.owner_of(&2), user_2);
+}
+
+#[test]
+fn disabling_bots_blocks_bot_battles() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    let admin = Address::random(&env);
+    initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    let name = Symbol::new(&env, "Austerlitz");
+
+    client.set_bots_enabled(&admin, &false);
+    assert!(!client.get_bots_enabled());
+    assert_eq!(
+        client.try_challenge_bot(&user_1, &name),
+        Err(Ok(BattleError::BotsDisabled.into()))
+    );
+    assert_eq!(
+        client.try_create_auto_battle(&name, &user_1),
+        Err(Ok(BattleError::BotsDisabled.into()))
+    );
+    assert!(client.try_tick(&1).is_err());
+
+    // PvP is unaffected
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+    assert_eq!(client.get_battle(&name).battle_status, 1);
+
+    client.set_bots_enabled(&admin, &true);
+    let user_3 = Address::random(&env);
+    client.add_player(&user_3);
+    client.challenge_bot(&user_3, &Symbol::new(&env, "Jena"));
+}
+
+#[test]
+fn only_the_creator_can_hand_a_battle_to_the_bot() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    client.add_players(&vec![&env, user_1.clone(), user_2.clone()]);
+    let name = Symbol::new(&env, "Eylau");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+
+    // Nobody else can take over the creator's battle
+    assert!(client.try_challenge_bot(&user_2, &name).is_err());
+    assert_eq!(client.get_player_battle(&user_1), Some(name.clone()));
+
+    client.challenge_bot(&user_1, &name);
+    let battle = client.get_battle(&name);
+    assert_eq!(battle.battle_status, 1);
+    assert_eq!(
+        battle.players,
+        map![&env, (user_1.clone(), 1), (contract_id.clone(), 2)]
+    );
+
+    // A bot battle under a new name is listed, so tick can resolve it
+    let fresh = Symbol::new(&env, "Friedland");
+    client.challenge_bot(&user_2, &fresh);
+    assert!(client.get_battles().contains(fresh.clone()));
+    client.attack_or_defend_choice(&user_2, &2, &fresh);
+    assert_eq!(client.tick(&10), 1);
+    assert_eq!(client.get_battle(&fresh).rounds.len(), 1);
+}
+
+#[test]
+fn pausing_blocks_new_battles_but_not_running_ones() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    let admin = Address::random(&env);
+    initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    let user_3 = Address::random(&env);
+    client.add_player(&user_3);
+    let name = Symbol::new(&env, "Lepanto");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+
+    assert!(client.try_set_paused(&user_1, &true).is_err());
+    client.set_paused(&admin, &true);
+    assert!(client.get_paused());
+    assert!(client
+        .try_create_battle(&Symbol::new(&env, "Salamis"), &user_3, &2)
+        .is_err());
+    assert_eq!(
+        client.try_challenge_bot(&user_3, &Symbol::new(&env, "Salamis")),
+        Err(Ok(BattleError::ContractPaused.into()))
+    );
+    assert_eq!(
+        client.try_forge_blade(&user_3, &1),
+        Err(Ok(BattleError::ContractPaused.into()))
+    );
+    assert!(client.try_join_matchmaking(&user_3).is_err());
+
+    // The battle already underway can still be fought to the end
+    fight_to_the_end_started(&client, &name, &user_1, &user_2);
+    assert_eq!(client.get_battle(&name).battle_status, 2);
+
+    client.set_paused(&admin, &false);
+    client.forge_blade(&user_3, &1);
+}
+
+#[test]
+fn minted_balance_is_persisted() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let (sword, _admin, minter) = setup_sword_contract(&env);
+    let user_1 = Address::random(&env);
+
+    sword.mint_nft(&minter, &user_1, &3, &2);
+    assert_eq!(sword.balance_of(&user_1, &3), 2);
+    assert_eq!(sword.balance_of(&user_1, &1), 0);
+}
+
+#[test]
+fn timeout_winner_preview() {
+    let (
+        env,
+        _contract_id,
+        user_1,
+        _user_2,
+        _class_1,
+        _class_2,
+        attack,
+        _defend,
+        battle_name,
+        client,
+    ) = setup_battle_sequence();
+    assert_eq!(client.timeout_winner(&battle_name), None);
+
+    env.ledger().with_mut(|li| li.timestamp = 1_000);
+    client.attack_or_defend_choice(&user_1, &attack.unwrap(), &battle_name);
+    env.ledger()
+        .with_mut(|li| li.timestamp = 1_000 + MOVE_TIMEOUT);
+    assert_eq!(client.timeout_winner(&battle_name), None);
+
+    env.ledger()
+        .with_mut(|li| li.timestamp = 1_001 + MOVE_TIMEOUT);
+    assert_eq!(client.timeout_winner(&battle_name), Some(user_1.clone()));
+    client.claim_timeout_win(&user_1, &battle_name);
+    assert_eq!(client.timeout_winner(&battle_name), None);
+}
+
+#[test]
+fn melting_requires_an_owned_sword() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let (sword, _admin, minter) = setup_sword_contract(&env);
+    let user_1 = Address::random(&env);
+
+    sword.mint_nft(&minter, &user_1, &1, &1);
+    sword.melt_blade(&minter, &user_1, &1);
+    assert_eq!(sword.balance_of(&user_1, &1), 0);
+
+    // Neither the melted sword nor one never minted can be burned
+    assert!(sword.try_melt_blade(&minter, &user_1, &1).is_err());
+    assert!(sword.try_melt_blade(&minter, &user_1, &2).is_err());
+}
+
+#[test]
+fn melting_another_class_is_rejected() {
+    let (_env, _contract_id, user_1, _user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.forge_blade(&user_1, &3);
+
+    assert!(client.try_melt_blade(&user_1, &1).is_err());
+    let stats = client.get_player_stats(&user_1);
+    assert!(stats.has_sword);
+    assert_eq!(stats.sword_class, 3);
+    client.melt_blade(&user_1, &3);
+}
+
+#[test]
+fn heal_sources_share_the_cap() {
+    let (env, contract_id, user_1, user_2, _class_1, _class_2, attack, defend, battle_name, client) =
+        setup_battle_sequence();
+
+    // A player already above the cap isn't healed further, nor pulled down
+    let mut stats = client.get_player_stats(&user_1);
+    stats.health = 120;
+    env.as_contract(&contract_id, || {
+        BattleContract::set_player_stats(env.clone(), user_1.clone(), stats).unwrap()
+    });
+    client.attack_or_defend_choice(&user_1, &defend.unwrap(), &battle_name);
+    client.attack_or_defend_choice(&user_2, &defend.unwrap(), &battle_name);
+    assert_eq!(client.get_player_stats(&user_1).health, 120);
+    assert_eq!(client.get_player_stats(&user_2).health, 108);
+
+    // Defense that fully absorbs an attack doesn't heal past the cap either
+    let mut stats = client.get_player_stats(&user_1);
+    stats.attack = 5;
+    env.as_contract(&contract_id, || {
+        BattleContract::set_player_stats(env.clone(), user_1.clone(), stats).unwrap()
+    });
+    client.attack_or_defend_choice(&user_1, &attack.unwrap(), &battle_name);
+    client.attack_or_defend_choice(&user_2, &defend.unwrap(), &battle_name);
+    assert_eq!(client.get_player_stats(&user_2).health, 108);
+}
+
+#[test]
+fn deauthorized_holders_are_frozen() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let (sword, admin, minter) = setup_sword_contract(&env);
+    let user_1 = Address::random(&env);
+    sword.mint_nft(&minter, &user_1, &1, &1);
+
+    // Only the admin may freeze a holder
+    assert!(sword.try_set_authorized(&user_1, &user_1, &false).is_err());
+    sword.set_authorized(&admin, &user_1, &false);
+    assert!(sword.try_mint_nft(&minter, &user_1, &2, &1).is_err());
+    assert!(sword.try_melt_blade(&minter, &user_1, &1).is_err());
+
+    sword.set_authorized(&admin, &user_1, &true);
+    sword.mint_nft(&minter, &user_1, &2, &1);
+    sword.melt_blade(&minter, &user_1, &1);
+    assert_eq!(sword.balance_of(&user_1, &1), 0);
+    assert_eq!(sword.balance_of(&user_1, &2), 1);
+}
+
+#[test]
+fn inventory_lists_held_swords() {
+    let (env, contract_id, user_1, _user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.forge_blade(&user_1, &2);
+    env.as_contract(&contract_id, || {
+        sword_contract::mint(&env, user_1.clone(), 3, 1).unwrap()
+    });
+
+    let inventory = client.inventory(&user_1);
+    assert_eq!(inventory.len(), 2);
+    let sabre = inventory.get(0).unwrap();
+    assert_eq!((sabre.token_id, sabre.class), (1, 2));
+    let sabre_metadata = env.as_contract(&contract_id, || {
+        SwordContract::get_token_metadata(env.clone(), 2)
+    });
+    assert_eq!(sabre.metadata, sabre_metadata);
+    assert_eq!(
+        (sabre.health_bonus, sabre.attack_bonus, sabre.defense_bonus),
+        (-3, 16, 2)
+    );
+    let claymore = inventory.get(1).unwrap();
+    assert_eq!((claymore.token_id, claymore.class), (2, 3));
+    assert_eq!(
+        (
+            claymore.health_bonus,
+            claymore.attack_bonus,
+            claymore.defense_bonus
+        ),
+        (7, 11, -3)
+    );
+
+    client.melt_blade(&user_1, &2);
+    assert_eq!(client.inventory(&user_1).len(), 1);
+}
+
+#[test]
+fn total_supply_follows_mints_and_melts() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let (sword, _admin, minter) = setup_sword_contract(&env);
+    let user_1 = Address::random(&env);
+    let user_2 = Address::random(&env);
+
+    sword.mint_nft(&minter, &user_1, &1, &2);
+    sword.mint_nft(&minter, &user_2, &1, &1);
+    sword.mint_nft(&minter, &user_2, &3, &1);
+    assert_eq!(sword.total_supply(&1), 3);
+    assert_eq!(sword.total_supply(&3), 1);
+
+    sword.melt_blade(&minter, &user_1, &1);
+    sword.melt_blade(&minter, &user_2, &3);
+    assert_eq!(sword.total_supply(&1), 2);
+    assert_eq!(sword.total_supply(&3), 0);
+    assert_eq!(sword.total_supply(&2), 0);
+}
+
+#[test]
+fn watch_battle_respects_spectator_cap() {
+    let (
+        env,
+        contract_id,
+        user_1,
+        _user_2,
+        _class_1,
+        _class_2,
+        _attack,
+        _defend,
+        battle_name,
+        client,
+    ) = setup_battle_sequence();
+    assert_eq!(client.get_max_spectators(), DEFAULT_MAX_SPECTATORS);
+    let admin = Address::random(&env);
+    initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    client.set_max_spectators(&admin, &2);
+
+    let spectator_1 = Address::random(&env);
+    let spectator_2 = Address::random(&env);
+    client.watch_battle(&spectator_1, &battle_name);
+    client.watch_battle(&spectator_2, &battle_name);
+    // Watching again doesn't take another seat
+    client.watch_battle(&spectator_1, &battle_name);
+    assert_eq!(
+        client.get_spectators(&battle_name),
+        vec![&env, spectator_1, spectator_2]
+    );
+
+    assert!(client
+        .try_watch_battle(&Address::random(&env), &battle_name)
+        .is_err());
+    assert!(client
+        .try_watch_battle(&user_1, &Symbol::new(&env, "Nowhere"))
+        .is_err());
+}
+
+#[test]
+fn three_player_battle_resolves() {
+    let (env, contract_id, _user_1, _user_2, client) = setup_test();
+    pin_damage_variance(&env, &contract_id);
+    let name = Symbol::new(&env, "Waterloo");
+    let users = [
+        Address::random(&env),
+        Address::random(&env),
+        Address::random(&env),
+    ];
+    for user in users.iter() {
+        client.add_player(user);
+        client.forge_blade(user, &1);
+    }
+    assert!(client.try_create_battle(&name, &users[0], &5).is_err());
+
+    assert_eq!(client.create_battle(&name, &users[0], &3), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &users[1]), (Ok(()), Ok(())));
+    assert_eq!(client.get_battle(&name).battle_status, 0);
+    assert_eq!(client.join_battle(&name, &users[2]), (Ok(()), Ok(())));
+    let battle = client.get_battle(&name);
+    assert_eq!(battle.battle_status, 1);
+    for (slot, user) in users.iter().enumerate() {
+        assert_eq!(battle.players.get(user.clone()), Some(slot as u64 + 1));
+    }
+
+    // Everyone targets the opponent with the lowest health, the first in
+    // player order on ties: p0 hits p1 while p1 and p2 both hit p0
+    let order = battle.players.keys();
+    let (p0, p1, p2) = (
+        order.get(0).unwrap(),
+        order.get(1).unwrap(),
+        order.get(2).unwrap(),
+    );
+    let attack_all = |client: &BattleContractClient<'static>| {
+        for player in client.get_battle(&name).players.keys().iter() {
+            client.attack_or_defend_choice(&player, &1, &name);
+        }
+    };
+    attack_all(&client);
+    assert_eq!(client.get_battle(&name).turns, 0);
+    assert_eq!(client.get_player_stats(&p0).health, 92);
+    assert_eq!(client.get_player_stats(&p1).health, 100);
+    assert_eq!(client.get_player_stats(&p2).health, 108);
+
+    // p0 keeps taking two hits a round and is knocked out in the seventh
+    for _ in 0..6 {
+        attack_all(&client);
+    }
+    let battle = client.get_battle(&name);
+    assert_eq!(battle.players.len(), 2);
+    assert!(!battle.players.contains_key(p0.clone()));
+    let p0_stats = client.get_player_stats(&p0);
+    assert!(!p0_stats.in_battle);
+    assert_eq!(p0_stats.losses, 1);
+    // Knocked out players leave at full health, Longsword bonus included
+    assert_eq!(p0_stats.health, 108);
+    assert_eq!(client.get_battle_history(&name).len(), 7);
+
+    // The last two fight it out under the two-player rules
+    while client.get_battle(&name).battle_status == 1 {
+        attack_all(&client);
+    }
+    let battle = client.get_battle(&name);
+    assert_eq!(battle.winner, OptionalAddress::Some(p2.clone()));
+    assert_eq!(client.get_player_stats(&p2).wins, 1);
+    assert_eq!(client.get_player_stats(&p1).losses, 1);
+    // The log names the two players who were left, whatever their slots
+    let entry = client.get_battle_log(&0, &1).get_unchecked(0);
+    let logged = [entry.player_a, entry.player_b];
+    assert!(logged.contains(&p1) && logged.contains(&p2));
+    for user in users.iter() {
+        assert!(!client.get_player_stats(user).in_battle);
+    }
+}
+
+#[test]
+fn cancel_pending_battle() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    let name = Symbol::new(&env, "Lutzen");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+
+    // Only the creator can back out
+    assert!(client.try_cancel_battle(&user_2, &name).is_err());
+    client.cancel_battle(&user_1, &name);
+    assert!(!client.get_player_stats(&user_1).in_battle);
+    assert!(!client.get_battles().contains(name.clone()));
+    assert!(client.try_cancel_battle(&user_1, &name).is_err());
+
+    // The name is free again, but a started battle can't be cancelled
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+    assert!(client.try_cancel_battle(&user_1, &name).is_err());
+    assert!(client.get_player_stats(&user_1).in_battle);
+}
+
+#[test]
+fn first_win_timestamp_is_kept() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &2);
+    client.forge_blade(&user_2, &1);
+    assert_eq!(client.get_first_win_at(&user_1), None);
+
+    env.ledger().with_mut(|li| li.timestamp = 5_000);
+    fight_to_the_end(&client, &Symbol::new(&env, "Poitiers"), &user_1, &user_2);
+    assert_eq!(client.get_first_win_at(&user_1), Some(5_000));
+    assert_eq!(client.get_first_win_at(&user_2), None);
+
+    env.ledger().with_mut(|li| li.timestamp = 9_000);
+    fight_to_the_end(&client, &Symbol::new(&env, "Sluys"), &user_1, &user_2);
+    assert_eq!(client.get_player_stats(&user_1).wins, 2);
+    assert_eq!(client.get_first_win_at(&user_1), Some(5_000));
+}
+
+#[test]
+fn initialize_stores_admin_and_sword_contract() {
+    let (env, contract_id, _user_1, _user_2, client) = setup_test();
+    let admin = Address::random(&env);
+
+    // Admin-only configuration has nobody to authorize it before initialization
+    assert!(client.try_get_admin().is_err());
+    assert!(client.try_get_sword_contract().is_err());
+    assert!(client.try_set_bots_enabled(&admin, &false).is_err());
+    assert!(client.try_set_max_spectators(&admin, &1).is_err());
+    // The contract can't stand in for its own sword contract
+    assert!(client
+        .try_initialize(&admin, &contract_id, &Address::random(&env))
+        .is_err());
+
+    let sword = initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    assert_eq!(client.get_admin(), admin);
+    assert_eq!(client.get_sword_contract(), sword.address);
+
+    client.set_bots_enabled(&admin, &false);
+    client.set_max_spectators(&admin, &1);
+    assert_eq!(env.auths()[0].0, admin);
+    assert!(!client.get_bots_enabled());
+    assert_eq!(client.get_max_spectators(), 1);
+}
+
+#[test]
+fn swords_are_minted_and_melted_on_the_sword_contract() {
+    let (env, contract_id, user_1, _user_2, client) = setup_test();
+    let admin = Address::random(&env);
+    let sword = initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    client.add_player(&user_1);
+
+    client.forge_blade(&user_1, &1);
+    assert_eq!(sword.balance_of(&user_1, &1), 1);
+
+    // A second Longsword from elsewhere, held on both ledgers
+    env.as_contract(&contract_id, || {
+        sword_contract::mint(&env, user_1.clone(), 1, 1).unwrap()
+    });
+    sword.mint_nft(&admin, &user_1, &1, &1);
+    client.fuse_blades(&user_1, &1);
+    let enhanced = 1 + ENHANCED_CLASS_OFFSET;
+    assert_eq!(sword.balance_of(&user_1, &1), 0);
+    assert_eq!(sword.balance_of(&user_1, &enhanced), 1);
+
+    client.melt_blade(&user_1, &enhanced);
+    assert_eq!(sword.balance_of(&user_1, &enhanced), 0);
+    assert_eq!(sword.total_supply(&enhanced), 0);
+}
+
+#[test]
+fn add_and_remove_players() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &2);
+
+    // Registering again neither duplicates the player nor resets their stats
+    client.add_player(&user_1);
+    assert_eq!(
+        client.get_players(),
+        vec![&env, user_1.clone(), user_2.clone()]
+    );
+    assert!(client.get_player_stats(&user_1).has_sword);
+
+    client.remove_player(&user_1);
+    assert_eq!(client.get_players(), vec![&env, user_2.clone()]);
+    assert_eq!(client.get_player_stats(&user_1).player_address, contract_id);
+
+    // Players can't walk away from a battle in progress
+    assert_eq!(
+        client.create_battle(&Symbol::new(&env, "Rocroi"), &user_2, &2),
+        (Ok(()), Ok(()))
+    );
+    assert!(client.try_remove_player(&user_2).is_err());
+}
+
+#[test]
+fn last_round_damage_matches_health_changes() {
+    let (
+        _env,
+        _contract_id,
+        user_1,
+        user_2,
+        _class_1,
+        _class_2,
+        attack,
+        defend,
+        battle_name,
+        client,
+    ) = setup_battle_sequence();
+    assert_eq!(client.last_round_damage(&battle_name).len(), 0);
+
+    client.attack_or_defend_choice(&user_1, &attack.unwrap(), &battle_name);
+    client.attack_or_defend_choice(&user_2, &attack.unwrap(), &battle_name);
+    let log = client.last_round_damage(&battle_name);
+    assert_eq!(log.len(), 2);
+    for (player, dealt, taken) in log.iter() {
+        assert_eq!(client.get_player_stats(&player).health, 108 - taken);
+        assert_eq!((dealt, taken), (8, 8));
+    }
+
+    // Only the hit that gets through the defense counts
+    client.attack_or_defend_choice(&user_1, &attack.unwrap(), &battle_name);
+    client.attack_or_defend_choice(&user_2, &defend.unwrap(), &battle_name);
+    let log = client.last_round_damage(&battle_name);
+    assert!(log.contains((user_1.clone(), 1, 0)));
+    assert!(log.contains((user_2.clone(), 0, 1)));
+    assert_eq!(client.get_player_stats(&user_2).health, 99);
+}
+
+#[test]
+fn forge_limit_caps_a_class_per_player() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    let admin = Address::random(&env);
+    initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    assert_eq!(client.get_forge_limit(&3), None);
+    client.set_forge_limit(&admin, &3, &1);
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+
+    client.forge_blade(&user_1, &3);
+    client.melt_blade(&user_1, &3);
+    assert_eq!(
+        client.try_forge_blade(&user_1, &3),
+        Err(Ok(BattleError::ForgeLimitReached.into()))
+    );
+
+    // Other classes and other players are unaffected
+    client.forge_blade(&user_1, &1);
+    client.forge_blade(&user_2, &3);
+}
+
+#[test]
+fn battle_filters_split_by_status() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    let user_3 = Address::random(&env);
+    for user in [&user_1, &user_2, &user_3] {
+        client.add_player(user);
+        client.forge_blade(user, &1);
+    }
+    let ended = Symbol::new(&env, "Hadrian");
+    let started = Symbol::new(&env, "Trajan");
+    let pending = Symbol::new(&env, "Nerva");
+
+    fight_to_the_end(&client, &ended, &user_1, &user_2);
+    assert_eq!(
+        client.create_battle(&started, &user_1, &2),
+        (Ok(()), Ok(()))
+    );
+    assert_eq!(client.join_battle(&started, &user_2), (Ok(()), Ok(())));
+    assert_eq!(
+        client.create_battle(&pending, &user_3, &2),
+        (Ok(()), Ok(()))
+    );
+
+    assert_eq!(client.get_battles().len(), 2);
+    assert_eq!(client.get_active_battles(), vec![&env, started]);
+    assert_eq!(client.get_open_battles(), vec![&env, pending]);
+}
+
+#[test]
+fn treasury_summary_reports_contract_balances() {
+    let (env, contract_id, _user_1, _user_2, client) = setup_test();
+    let token_admin = Address::random(&env);
+    let fees = env.register_stellar_asset_contract(token_admin.clone());
+    let unused = env.register_stellar_asset_contract(token_admin);
+
+    token::StellarAssetClient::new(&env, &fees).mint(&contract_id, &250);
+
+    assert_eq!(
+        client.treasury_summary(&vec![&env, fees.clone(), unused.clone()]),
+        vec![&env, (fees, 250_i128), (unused, 0_i128)]
+    );
+}
+
+#[test]
+fn ended_battles_move_to_the_archive() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &2);
+    client.forge_blade(&user_2, &1);
+    let name = Symbol::new(&env, "Aurelian");
+
+    assert_eq!(client.get_battle_archive(), vec![&env]);
+    fight_to_the_end(&client, &name, &user_1, &user_2);
+
+    assert!(!client.get_battles().contains(name.clone()));
+    assert_eq!(client.get_battle_archive(), vec![&env, name.clone()]);
+    assert_eq!(
+        client.get_battle(&name).winner,
+        OptionalAddress::Some(user_1.clone())
+    );
+}
+
+#[test]
+fn joining_your_own_battle_is_rejected() {
+    let (env, _contract_id, user_1, _user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.forge_blade(&user_1, &1);
+    let name = Symbol::new(&env, "Aurelian");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+
+    assert_eq!(
+        client.try_join_battle(&name, &user_1),
+        Err(Ok(BattleError::CannotBattleSelf.into()))
+    );
+    assert_eq!(client.get_battle(&name).battle_status, 0);
+}
+
+#[test]
+fn valid_moves_follow_the_round() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &1);
+    client.forge_blade(&user_2, &1);
+    let name = Symbol::new(&env, "Aurelian");
+    let none: Vec<u64> = vec![&env];
+
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.valid_moves(&name, &user_1), none);
+
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+    assert_eq!(client.valid_moves(&name, &user_1), vec![&env, 1, 2, 3]);
+
+    client.attack_or_defend_choice(&user_1, &1, &name);
+    assert_eq!(client.valid_moves(&name, &user_1), none);
+    assert_eq!(client.valid_moves(&name, &user_2), vec![&env, 1, 2, 3]);
+    assert_eq!(client.valid_moves(&name, &Address::random(&env)), none);
+
+    // A player who left a free-for-all owes no more moves
+    let users = [
+        Address::random(&env),
+        Address::random(&env),
+        Address::random(&env),
+    ];
+    client.add_players(&vec![
+        &env,
+        users[0].clone(),
+        users[1].clone(),
+        users[2].clone(),
+    ]);
+    let brawl = Symbol::new(&env, "Carrhae");
+    assert_eq!(
+        client.create_battle(&brawl, &users[0], &3),
+        (Ok(()), Ok(()))
+    );
+    assert_eq!(client.join_battle(&brawl, &users[1]), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&brawl, &users[2]), (Ok(()), Ok(())));
+    assert_eq!(client.valid_moves(&brawl, &users[2]), vec![&env, 1, 2, 3]);
+    client.forfeit_battle(&users[2], &brawl);
+    assert_eq!(client.get_battle(&brawl).battle_status, 1);
+    assert_eq!(client.valid_moves(&brawl, &users[2]), none);
+    assert_eq!(client.valid_moves(&brawl, &users[0]), vec![&env, 1, 2, 3]);
+}
+
+#[test]
+fn repair_blade_restores_durability() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &2);
+    client.forge_blade(&user_2, &1);
+
+    fight_to_the_end(&client, &Symbol::new(&env, "Aurelian"), &user_1, &user_2);
+    assert_eq!(client.get_player_stats(&user_1).durability, 14);
+    client.repair_blade(&user_1, &2);
+    assert_eq!(client.get_player_stats(&user_1).durability, 15);
+
+    // Only the sword the player holds can be repaired, and not mid-battle
+    assert!(client.try_repair_blade(&user_1, &1).is_err());
+    let name = Symbol::new(&env, "Hadrian");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert!(client.try_repair_blade(&user_1, &2).is_err());
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+
+    // A sword on its last battle breaks and has to be forged again
+    env.as_contract(&contract_id, || {
+        let mut stats = BattleContract::get_player_stats(env.clone(), user_2.clone());
+        stats.durability = 1;
+        BattleContract::set_player_stats(env.clone(), user_2.clone(), stats).unwrap();
+    });
+    fight_to_the_end_started(&client, &name, &user_1, &user_2);
+    let broken = client.get_player_stats(&user_2);
+    assert!(!broken.has_sword);
+    assert_eq!((broken.attack, broken.defense), (10, 10));
+    assert!(client.try_repair_blade(&user_2, &1).is_err());
+    client.forge_blade(&user_2, &1);
+
+    let no_sword = Address::random(&env);
+    client.add_player(&no_sword);
+    assert!(client.try_repair_blade(&no_sword, &1).is_err());
+}
+
+#[test]
+fn only_fought_battles_wear_swords_down() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_players(&vec![&env, user_1.clone(), user_2.clone()]);
+    client.forge_blade(&user_1, &1);
+    client.forge_blade(&user_2, &1);
+    let fresh = client.get_player_stats(&user_1).durability;
+
+    let cancelled = Symbol::new(&env, "Agincourt");
+    assert_eq!(
+        client.create_battle(&cancelled, &user_1, &2),
+        (Ok(()), Ok(()))
+    );
+    client.cancel_battle(&user_1, &cancelled);
+    let declined = Symbol::new(&env, "Crecy");
+    client.invite_player(&declined, &user_1, &user_2);
+    client.decline_invite(&user_2, &declined);
+    let forfeited = Symbol::new(&env, "Poitiers");
+    assert_eq!(
+        client.create_battle(&forfeited, &user_1, &2),
+        (Ok(()), Ok(()))
+    );
+    assert_eq!(client.join_battle(&forfeited, &user_2), (Ok(()), Ok(())));
+    client.forfeit_battle(&user_2, &forfeited);
+    for user in [&user_1, &user_2] {
+        assert_eq!(client.get_player_stats(user).durability, fresh);
+    }
+
+    fight_to_the_end(&client, &Symbol::new(&env, "Sluys"), &user_1, &user_2);
+    for user in [&user_1, &user_2] {
+        assert_eq!(client.get_player_stats(user).durability, fresh - 1);
+    }
+}
+
+#[test]
+fn broken_swords_are_burned_and_cannot_be_fused() {
+    let (env, contract_id, _user_1, _user_2, client) = setup_test();
+    let (name, user_1, user_2) = start_sword_battle(&env, &client, "Blenheim", 1, 1);
+    env.as_contract(&contract_id, || {
+        sword_contract::mint(&env, user_2.clone(), 1, 1).unwrap();
+        let mut stats = BattleContract::get_player_stats(env.clone(), user_2.clone());
+        stats.durability = 1;
+        BattleContract::set_player_stats(env.clone(), user_2.clone(), stats).unwrap();
+    });
+
+    fight_to_the_end_started(&client, &name, &user_1, &user_2);
+    assert!(!client.get_player_stats(&user_2).has_sword);
+    // Only the spare is left, so there is nothing to fuse it with
+    let balance = env.as_contract(&contract_id, || {
+        SwordContract::balance_of(env.clone(), user_2.clone(), 1)
+    });
+    assert_eq!(balance, 1);
+    assert!(client.try_fuse_blades(&user_2, &1).is_err());
+}
+
+#[test]
+fn revealed_seed_steers_bot_randomness() {
+    let (env, contract_id, user_1, _user_2, client) = setup_test();
+    let name = Symbol::new(&env, "Marathon");
+    client.add_player(&user_1);
+    client.create_auto_battle(&name, &user_1);
+
+    // Pick a seed that changes the bot's first move
+    let seed = env.as_contract(&contract_id, || {
+        let unseeded = BattleContract::bot_move(&env, 0);
+        (1..64)
+            .find(|seed| BattleContract::bot_move(&env, *seed) != unseeded)
+            .unwrap()
+    });
+    let commit = env
+        .crypto()
+        .sha256(&Bytes::from_array(&env, &seed.to_be_bytes()));
+    client.commit_battle_seed(&name, &user_1, &commit);
+
+    assert_eq!(
+        client.try_reveal_battle_seed(&name, &user_1, &(seed + 1)),
+        Err(Ok(BattleError::SeedMismatch.into()))
+    );
+    client.reveal_battle_seed(&name, &user_1, &seed);
+    assert_eq!(client.get_battle(&name).revealed_seed, seed);
+
+    client.attack_or_defend_choice(&user_1, &2, &name);
+    assert_eq!(client.tick(&1), 1);
+    let bot_move = client
+        .get_battle(&name)
+        .rounds
+        .get(0)
+        .unwrap()
+        .moves
+        .get(contract_id.clone())
+        .unwrap();
+    let expected = env.as_contract(&contract_id, || BattleContract::bot_move(&env, seed));
+    assert_eq!(bot_move, expected);
+}
+
+#[test]
+fn revealed_seed_changes_damage_variance() {
+    let (env, contract_id, _user_1, _user_2, client) = setup_test();
+    let (name, user_1, _user_2) = start_sword_battle(&env, &client, "Plataea", 2, 2);
+    let battle = client.get_battle(&name);
+    let stat = client.get_player_stats(&user_1);
+
+    // Some revealed seed rolls a different attack than no seed at all
+    env.as_contract(&contract_id, || {
+        let attack_with = |seed: u64| {
+            let mut battle = battle.clone();
+            battle.revealed_seed = seed;
+            BattleContract::effective_attack(&env, &battle, &user_1, &stat)
+        };
+        let unseeded = attack_with(0);
+        assert!((1..64).any(|seed| attack_with(seed) != unseeded));
+    });
+}
+
+#[test]
+fn committed_battles_wait_for_the_reveal() {
+    let (env, _contract_id, _user_1, _user_2, client) = setup_test();
+    let (name, user_1, user_2) = start_sword_battle(&env, &client, "Salamis", 1, 1);
+    let seed = 7u64;
+    let commit = env
+        .crypto()
+        .sha256(&Bytes::from_array(&env, &seed.to_be_bytes()));
+    client.commit_battle_seed(&name, &user_1, &commit);
+
+    client.attack_or_defend_choice(&user_1, &1, &name);
+    client.attack_or_defend_choice(&user_2, &1, &name);
+    assert!(client.get_battle(&name).rounds.is_empty());
+
+    client.reveal_battle_seed(&name, &user_1, &seed);
+    let battle = client.get_battle(&name);
+    assert_eq!(battle.rounds.len(), 1);
+    assert_eq!(battle.turns, 0);
+}
+
+#[test]
+fn an_unrevealed_seed_forfeits_the_battle() {
+    let (env, _contract_id, _user_1, _user_2, client) = setup_test();
+    let (name, user_1, user_2) = start_sword_battle(&env, &client, "Mycale", 1, 1);
+    let commit = env
+        .crypto()
+        .sha256(&Bytes::from_array(&env, &7u64.to_be_bytes()));
+    client.commit_battle_seed(&name, &user_1, &commit);
+    client.attack_or_defend_choice(&user_1, &1, &name);
+    client.attack_or_defend_choice(&user_2, &1, &name);
+    assert_eq!(client.timeout_winner(&name), None);
+
+    env.ledger().with_mut(|li| li.timestamp = MOVE_TIMEOUT + 1);
+    assert_eq!(client.timeout_winner(&name), Some(user_2.clone()));
+    client.claim_timeout_win(&user_2, &name);
+    let battle = client.get_battle(&name);
+    assert_eq!(battle.winner, OptionalAddress::Some(user_2.clone()));
+    assert!(battle.rounds.is_empty());
+}
+
+#[test]
+fn forge_blade_rejects_unknown_classes() {
+    let (_env, _contract_id, user_1, _user_2, client) = setup_test();
+    client.add_player(&user_1);
+
+    // Class 0 means no sword, and enhanced classes can only be fused
+    assert!(client.try_forge_blade(&user_1, &0).is_err());
+    assert!(client.try_forge_blade(&user_1, &4).is_err());
+    assert!(!client.get_player_stats(&user_1).has_sword);
+    client.forge_blade(&user_1, &3);
+    assert_eq!(client.get_player_stats(&user_1).sword_class, 3);
+}
+
+#[test]
+fn forge_blade_requires_full_health() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+
+    client.forge_blade(&user_1, &2);
+    assert_eq!(client.get_player_stats(&user_1).health, MAX_HEALTH - 3);
+
+    env.as_contract(&contract_id, || {
+        let mut stats = BattleContract::get_player_stats(env.clone(), user_2.clone());
+        stats.health = 2;
+        BattleContract::set_player_stats(env.clone(), user_2.clone(), stats).unwrap();
+    });
+    assert!(client.try_forge_blade(&user_2, &2).is_err());
+    assert!(!client.get_player_stats(&user_2).has_sword);
+}
+
+#[test]
+fn flat_battle_matches_the_battle() {
+    let (env, _contract_id, user_1, user_2, _, _, _, _, battle_name, client) =
+        setup_battle_sequence();
+    client.attack_or_defend_choice(&user_1, &1, &battle_name);
+    client.attack_or_defend_choice(&user_2, &1, &battle_name);
+    client.attack_or_defend_choice(&user_2, &2, &battle_name);
+
+    let battle = client.get_battle(&battle_name);
+    assert_eq!(
+        client.get_battle_flat(&battle_name),
+        FlatBattle {
+            name: Symbol::new(&env, "Constantine"),
+            status: BattleStatus::Started as u64,
+            player_a: OptionalAddress::Some(user_1.clone()),
+            move_a: 0,
+            player_b: OptionalAddress::Some(user_2.clone()),
+            move_b: 2,
+            winner: battle.winner,
+            round: 1,
+        }
+    );
+}
+
+#[test]
+fn attack_or_defend_choice_reports_specific_errors() {
+    let (env, contract_id, user_1, user_2, _, _, _, _, battle_name, client) =
+        setup_battle_sequence();
+    let err = |error: BattleError| Err(Ok(error.into()));
+
+    assert_eq!(
+        client.try_attack_or_defend_choice(&contract_id, &1, &battle_name),
+        err(BattleError::BotMoveNotAllowed)
+    );
+    assert_eq!(
+        client.try_attack_or_defend_choice(&user_1, &4, &battle_name),
+        err(BattleError::InvalidChoice)
+    );
+    assert_eq!(
+        client.try_attack_or_defend_choice(&Address::random(&env), &1, &battle_name),
+        err(BattleError::NotInBattle)
+    );
+    client.attack_or_defend_choice(&user_1, &1, &battle_name);
+    assert_eq!(
+        client.try_attack_or_defend_choice(&user_1, &1, &battle_name),
+        err(BattleError::AlreadyMoved)
+    );
+
+    let pending = Symbol::new(&env, "Nerva");
+    let user_3 = Address::random(&env);
+    client.add_player(&user_3);
+    assert_eq!(
+        client.create_battle(&pending, &user_3, &2),
+        (Ok(()), Ok(()))
+    );
+    assert_eq!(
+        client.try_attack_or_defend_choice(&user_3, &1, &pending),
+        err(BattleError::BattleNotStarted)
+    );
+    assert_eq!(client.get_battle(&battle_name).moves.get(user_2), Some(0));
+}
+
+#[test]
+fn base_stats_apply_to_new_players() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    let admin = Address::random(&env);
+    assert!(client.try_set_base_stats(&admin, &120, &12, &8).is_err());
+    client.add_player(&user_1);
+
+    initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    // Zero health and stats past their caps are rejected
+    assert!(client.try_set_base_stats(&admin, &0, &12, &8).is_err());
+    assert!(client
+        .try_set_base_stats(&admin, &(MAX_BASE_HEALTH + 1), &12, &8)
+        .is_err());
+    assert!(client
+        .try_set_base_stats(&admin, &120, &(MAX_ATTACK + 1), &8)
+        .is_err());
+    assert!(client
+        .try_set_base_stats(&admin, &120, &12, &u32::MAX)
+        .is_err());
+    client.set_base_stats(&admin, &120, &12, &8);
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(
+        client.get_base_stats(),
+        BaseStats {
+            health: 120,
+            attack: 12,
+            defense: 8,
+        }
+    );
+
+    client.add_player(&user_2);
+    let stats = client.get_player_stats(&user_2);
+    assert_eq!((stats.health, stats.attack, stats.defense), (120, 12, 8));
+    // Players registered earlier keep the stats they started with
+    let stats = client.get_player_stats(&user_1);
+    assert_eq!((stats.health, stats.attack, stats.defense), (100, 10, 10));
+}
+
+#[test]
+fn heals_stop_at_the_configured_base_health() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    let admin = Address::random(&env);
+    initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    client.set_base_stats(&admin, &50, &10, &10);
+    client.add_players(&vec![&env, user_1.clone(), user_2.clone()]);
+    client.forge_blade(&user_1, &1);
+    let name = Symbol::new(&env, "Rocroi");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+
+    for _ in 0..10 {
+        client.attack_or_defend_choice(&user_1, &2, &name);
+        client.attack_or_defend_choice(&user_2, &2, &name);
+    }
+    // The cap is the base health plus the Longsword's bonus, if any
+    assert_eq!(client.get_player_stats(&user_1).health, 58);
+    assert_eq!(client.get_player_stats(&user_2).health, 50);
+}
+
+#[test]
+fn forging_and_melting_keeps_base_stats() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    let admin = Address::random(&env);
+    initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    client.set_base_stats(&admin, &120, &12, &8);
+    client.add_player(&user_1);
+    // Later changes to the defaults don't affect registered players
+    client.set_base_stats(&admin, &100, &10, &10);
+    client.add_player(&user_2);
+
+    client.forge_blade(&user_1, &3);
+    let stats = client.get_player_stats(&user_1);
+    assert_eq!((stats.health, stats.attack, stats.defense), (127, 23, 5));
+    assert_eq!(
+        (stats.base_health, stats.base_attack, stats.base_defense),
+        (120, 12, 8)
+    );
+
+    client.melt_blade(&user_1, &3);
+    let stats = client.get_player_stats(&user_1);
+    assert_eq!((stats.health, stats.attack, stats.defense), (120, 12, 8));
+    assert_eq!(
+        (stats.base_health, stats.base_attack, stats.base_defense),
+        (120, 12, 8)
+    );
+
+    // A sword's penalty is removed exactly as it was applied
+    client.forge_blade(&user_2, &2);
+    client.melt_blade(&user_2, &2);
+    let stats = client.get_player_stats(&user_2);
+    assert_eq!((stats.health, stats.attack, stats.defense), (100, 10, 10));
+}
+
+#[test]
+fn damage_taken_accumulates_across_rounds() {
+    let (_env, _contract_id, user_1, user_2, _, _, _, _, battle_name, client) =
+        setup_battle_sequence();
+    let start = client.get_player_stats(&user_2).health;
+
+    client.attack_or_defend_choice(&user_1, &1, &battle_name);
+    client.attack_or_defend_choice(&user_2, &1, &battle_name);
+    client.attack_or_defend_choice(&user_1, &1, &battle_name);
+    client.attack_or_defend_choice(&user_2, &2, &battle_name);
+    let health = client.get_player_stats(&user_2).health;
+    assert_eq!(client.get_damage_taken(&user_2), (start - health) as u64);
+    assert_eq!(client.get_damage_taken(&user_1), 8);
+
+    // The knockout blow counts too
+    fight_to_the_end_started(&client, &battle_name, &user_1, &user_2);
+    let taken: u64 = client
+        .get_battle_history(&battle_name)
+        .iter()
+        .map(|round| round.taken.get(user_2.clone()).unwrap() as u64)
+        .sum();
+    assert_eq!(client.get_damage_taken(&user_2), taken);
+}
+
+#[test]
+fn attack_decays_each_round() {
+    let (env, contract_id, user_1, user_2, _, _, _, _, battle_name, client) =
+        setup_battle_sequence();
+    let admin = Address::random(&env);
+    initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    assert!(client.try_set_attack_decay(&admin, &101).is_err());
+    client.set_attack_decay(&admin, &10);
+
+    fight_to_the_end_started(&client, &battle_name, &user_1, &user_2);
+
+    // A longsword's 14 attack loses 10% per round, less half the opponent's
+    // 13 defense, until every hit deals the minimum of 1
+    let mut dealt = Vec::new(&env);
+    for round in client.get_battle_history(&battle_name).iter() {
+        dealt.push_back(round.dealt.get(user_1.clone()).unwrap());
+    }
+    assert_eq!(dealt.slice(..5), vec![&env, 8, 6, 5, 3, 2]);
+    assert!(dealt.slice(5..).iter().all(|hit| hit == 1));
+    assert_eq!(client.get_player_stats(&user_1).attack, 14);
+    assert_eq!(client.get_battle(&battle_name).battle_status, 2);
+}
+
+#[test]
+fn matchmaking_pairs_queued_players() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &1);
+    client.forge_blade(&user_2, &2);
+
+    assert_eq!(client.join_matchmaking(&user_1), None);
+    assert_eq!(client.join_matchmaking(&user_1), None);
+    assert!(!client.get_player_stats(&user_1).in_battle);
+
+    let name = client.join_matchmaking(&user_2).unwrap();
+    assert_eq!(name, Symbol::new(&env, "Match1"));
+    let battle = client.get_battle(&name);
+    assert_eq!(battle.battle_status, BattleStatus::Started as u64);
+    assert_eq!(
+        battle.players,
+        map![&env, (user_1.clone(), 1), (user_2.clone(), 2)]
+    );
+    assert!(client.get_player_stats(&user_1).in_battle);
+    assert!(client.get_player_stats(&user_2).in_battle);
+    assert_eq!(client.get_active_battles(), vec![&env, name.clone()]);
+
+    // The queue is empty again, so the next player waits
+    let user_3 = Address::random(&env);
+    client.add_player(&user_3);
+    assert_eq!(client.join_matchmaking(&user_3), None);
+}
+
+// The damage user_1 deals in the first round of a battle fought at `timestamp`
+fn first_hit_at(timestamp: u64) -> (u32, u32) {
+    let (env, contract_id, user_1, user_2, _, _, _, _, battle_name, client) =
+        setup_battle_sequence();
+    env.as_contract(&contract_id, || rng::set_override(&env, None));
+    env.ledger().with_mut(|ledger| ledger.timestamp = timestamp);
+    let expected = env.as_contract(&contract_id, || {
+        let battle = BattleContract::get_battle(env.clone(), battle_name.clone());
+        let stat = BattleContract::get_player_stats(env.clone(), user_1.clone());
+        let attack = BattleContract::effective_attack(&env, &battle, &user_1, &stat);
+        BattleContract::damage(attack, stat.defense, false)
+    });
+    client.attack_or_defend_choice(&user_1, &1, &battle_name);
+    client.attack_or_defend_choice(&user_2, &1, &battle_name);
+    let dealt = client
+        .last_round_damage(&battle_name)
+        .iter()
+        .find(|(player, _, _)| *player == user_1)
+        .unwrap()
+        .1;
+    (dealt, expected)
+}
+
+#[test]
+fn damage_varies_with_the_ledger() {
+    let (first, expected) = first_hit_at(1_000);
+    assert_eq!(first, expected);
+    // 80% to 120% of 14, or 150% of that on a crit, less half of 13 defense
+    assert!((5..=18).contains(&first));
+
+    let (second, expected) = (1_001..1_100)
+        .map(first_hit_at)
+        .find(|(dealt, _)| *dealt != first)
+        .unwrap();
+    assert_eq!(second, expected);
+}
+
+#[test]
+fn sabres_land_critical_hits() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &2);
+    client.forge_blade(&user_2, &1);
+    let name = Symbol::new(&env, "Cannae");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+    let dealt_by = |user: &Address| {
+        client
+            .last_round_damage(&name)
+            .iter()
+            .find(|(player, _, _)| player == user)
+            .unwrap()
+            .1
+    };
+
+    // Neither class crits on a low roll, and each hit loses half the
+    // target's defense
+    pin_damage_variance(&env, &contract_id);
+    client.attack_or_defend_choice(&user_1, &1, &name);
+    client.attack_or_defend_choice(&user_2, &1, &name);
+    assert_eq!(dealt_by(&user_1), 26 - 6);
+    assert_eq!(dealt_by(&user_2), 14 - 6);
+
+    // A roll of 84 keeps the variance at 100% but is a crit for the sabre
+    // (25%) and not for the longsword (5%)
+    env.as_contract(&contract_id, || rng::set_override(&env, Some(184)));
+    client.attack_or_defend_choice(&user_1, &1, &name);
+    client.attack_or_defend_choice(&user_2, &1, &name);
+    assert_eq!(dealt_by(&user_1), 39 - 6);
+    assert_eq!(dealt_by(&user_2), 14 - 6);
+}
+
+#[test]
+fn moves_remaining_counts_pending_players() {
+    let (_env, _contract_id, user_1, user_2, _, _, _, _, battle_name, client) =
+        setup_battle_sequence();
+    assert_eq!(client.moves_remaining(&battle_name), 2);
+
+    client.attack_or_defend_choice(&user_1, &1, &battle_name);
+    assert_eq!(client.moves_remaining(&battle_name), 1);
+
+    // Once both have moved the round resolves and the next one begins
+    client.attack_or_defend_choice(&user_2, &1, &battle_name);
+    assert_eq!(client.moves_remaining(&battle_name), 2);
+
+    fight_to_the_end_started(&client, &battle_name, &user_1, &user_2);
+    assert_eq!(client.moves_remaining(&battle_name), 0);
+}
+
+#[test]
+fn public_battle_hides_pending_moves() {
+    let (env, _contract_id, user_1, user_2, _, _, _, _, battle_name, client) =
+        setup_battle_sequence();
+    client.attack_or_defend_choice(&user_1, &2, &battle_name);
+
+    let seen_by_2 = client.get_battle_public(&battle_name, &user_2);
+    assert_eq!(seen_by_2.moves.get(user_1.clone()), Some(0));
+    let seen_by_1 = client.get_battle_public(&battle_name, &user_1);
+    assert_eq!(seen_by_1.moves.get(user_1.clone()), Some(2));
+    let seen_by_spectator = client.get_battle_public(&battle_name, &Address::random(&env));
+    assert_eq!(seen_by_spectator.moves.get(user_1.clone()), Some(0));
+
+    // The hidden move still counts when the round resolves
+    client.attack_or_defend_choice(&user_2, &1, &battle_name);
+    let round = client
+        .get_battle_public(&battle_name, &user_2)
+        .rounds
+        .get(0)
+        .unwrap();
+    assert_eq!(round.moves.get(user_1.clone()), Some(2));
+    assert_eq!(round.taken.get(user_1), Some(1));
+}
+
+#[test]
+fn defense_softens_every_hit() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    pin_damage_variance(&env, &contract_id);
+    let user_3 = Address::random(&env);
+    let user_4 = Address::random(&env);
+    for (user, class) in [(&user_1, 2), (&user_2, 1), (&user_3, 2), (&user_4, 3)] {
+        client.add_player(user);
+        client.forge_blade(user, &class);
+    }
+    let taken_by = |name: &Symbol, user: &Address| {
+        client
+            .last_round_damage(name)
+            .iter()
+            .find(|(player, _, _)| player == user)
+            .unwrap()
+            .2
+    };
+
+    // A sabre's 26 attack against a longsword's 13 defense and a claymore's 7
+    let high = Symbol::new(&env, "Thermopylae");
+    let low = Symbol::new(&env, "Marathon");
+    for (name, attacker, defender) in [(&high, &user_1, &user_2), (&low, &user_3, &user_4)] {
+        assert_eq!(client.create_battle(name, attacker, &2), (Ok(()), Ok(())));
+        assert_eq!(client.join_battle(name, defender), (Ok(()), Ok(())));
+        client.attack_or_defend_choice(attacker, &1, name);
+        client.attack_or_defend_choice(defender, &1, name);
+    }
+    assert_eq!(taken_by(&high, &user_2), 26 - 13 / 2);
+    assert_eq!(taken_by(&low, &user_4), 26 - 7 / 2);
+
+    // Defending blocks the full defense
+    for (name, attacker, defender) in [(&high, &user_1, &user_2), (&low, &user_3, &user_4)] {
+        client.attack_or_defend_choice(attacker, &1, name);
+        client.attack_or_defend_choice(defender, &2, name);
+    }
+    assert_eq!(taken_by(&high, &user_2), 26 - 13);
+    assert_eq!(taken_by(&low, &user_4), 26 - 7);
+}
+
+#[test]
+fn player_battle_follows_the_player() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &2);
+    client.forge_blade(&user_2, &1);
+    let name = Symbol::new(&env, "Zama");
+    assert_eq!(client.get_player_battle(&user_1), None);
+
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.get_player_battle(&user_1), Some(name.clone()));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+    assert_eq!(client.get_player_battle(&user_2), Some(name.clone()));
+
+    fight_to_the_end_started(&client, &name, &user_1, &user_2);
+    assert_eq!(client.get_player_battle(&user_1), None);
+    assert_eq!(client.get_player_battle(&user_2), None);
+}
+
+#[test]
+fn add_players_registers_everyone() {
+    let (env, _contract_id, user_1, _user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.forge_blade(&user_1, &2);
+    let mut users = vec![&env, user_1.clone()];
+    for _ in 0..4 {
+        users.push_back(Address::random(&env));
+    }
+
+    client.add_players(&users);
+    assert_eq!(env.auths().len(), 5);
+    assert_eq!(client.get_players(), users);
+    for user in users.slice(1..).iter() {
+        let stats = client.get_player_stats(&user);
+        assert_eq!((stats.health, stats.attack, stats.defense), (100, 10, 10));
+        assert!(!stats.has_sword);
+    }
+    // The already registered player keeps their sword
+    assert!(client.get_player_stats(&user_1).has_sword);
+}
+
+#[test]
+fn player_stats_pages() {
+    let (env, _contract_id, _user_1, _user_2, client) = setup_test();
+    let mut users = Vec::new(&env);
+    for _ in 0..MAX_PAGE_SIZE + 5 {
+        users.push_back(Address::random(&env));
+    }
+    client.add_players(&users);
+
+    let page = client.get_player_stats_page(&2, &3);
+    assert_eq!(page.len(), 3);
+    for (stat, user) in page.iter().zip(users.slice(2..5).iter()) {
+        assert_eq!(stat.player_address, user);
+    }
+
+    // The last page is cut short, and pages past the end are empty
+    assert_eq!(
+        client.get_player_stats_page(&(MAX_PAGE_SIZE + 3), &5).len(),
+        2
+    );
+    assert!(client
+        .get_player_stats_page(&(MAX_PAGE_SIZE + 5), &5)
+        .is_empty());
+    assert!(client
+        .get_player_stats_page(&u32::MAX, &u32::MAX)
+        .is_empty());
+
+    let page = client.get_player_stats_page(&1, &u32::MAX);
+    assert_eq!(page.len(), MAX_PAGE_SIZE);
+    assert_eq!(page.get_unchecked(0).player_address, users.get_unchecked(1));
+}
+
+#[test]
+fn human_battle_resolves_once_both_have_moved() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    let name = Symbol::new(&env, "Poitiers");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+    assert!(!client.get_battle(&name).players.contains_key(contract_id));
+
+    client.attack_or_defend_choice(&user_2, &1, &name);
+    assert!(client.get_battle_history(&name).is_empty());
+    client.attack_or_defend_choice(&user_1, &2, &name);
+
+    let battle = client.get_battle(&name);
+    assert_eq!(battle.rounds.len(), 1);
+    assert_eq!(battle.moves, map![&env, (user_1.clone(), 0), (user_2, 0)]);
+    assert_eq!(client.moves_remaining(&name), 2);
+}
+
+#[test]
+fn rematch_restarts_an_ended_battle() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &1);
+    let name = Symbol::new(&env, "Crecy");
+    fight_to_the_end(&client, &name, &user_1, &user_2);
+    let ended = client.get_battle(&name);
+    assert_eq!(ended.battle_status, 2);
+
+    client.rematch(&user_2, &name);
+    let battle = client.get_battle(&name);
+    assert_eq!(battle.battle_status, BattleStatus::Started as u64);
+    assert_eq!(battle.players, ended.players);
+    assert_eq!(
+        battle.moves,
+        map![&env, (user_1.clone(), 0), (user_2.clone(), 0)]
+    );
+    assert_eq!(battle.winner, OptionalAddress::None);
+    assert!(battle.rounds.is_empty());
+    assert_eq!(client.get_battles(), vec![&env, name.clone()]);
+    assert!(client.get_battle_archive().is_empty());
+    assert_eq!(client.get_player_stats(&user_1).health, 108);
+    assert_eq!(client.get_player_battle(&user_2), Some(name.clone()));
+
+    fight_to_the_end_started(&client, &name, &user_1, &user_2);
+    assert_eq!(client.get_battle(&name).battle_status, 2);
+
+    // A player who has since left can't be pulled back into a rematch
+    client.remove_player(&user_2);
+    assert_eq!(
+        client.try_rematch(&user_1, &name),
+        Err(Ok(BattleError::NotRegistered.into()))
+    );
+    assert!(!client.get_players().contains(user_2.clone()));
+}
+
+#[test]
+fn mutual_defense_heals_by_sword_class() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &1);
+    client.forge_blade(&user_2, &2);
+    let name = Symbol::new(&env, "Agincourt");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+
+    // Start wounded so there is room to heal
+    for user in [&user_1, &user_2] {
+        let mut stats = client.get_player_stats(user);
+        stats.health = 50;
+        env.as_contract(&contract_id, || {
+            BattleContract::set_player_stats(env.clone(), user.clone(), stats).unwrap()
+        });
+    }
+    client.attack_or_defend_choice(&user_1, &2, &name);
+    client.attack_or_defend_choice(&user_2, &2, &name);
+
+    // The Longsword wielder recovers more than the Sabre wielder
+    assert_eq!(client.get_player_stats(&user_1).health, 54);
+    assert_eq!(client.get_player_stats(&user_2).health, 51);
+}
+
+#[test]
+fn winner_is_only_known_once_the_battle_ends() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.forge_blade(&user_1, &2);
+    let name = Symbol::new(&env, "Tours");
+    assert_eq!(client.get_winner(&name), None);
+
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.get_winner(&name), None);
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+    assert_eq!(client.get_winner(&name), None);
+
+    fight_to_the_end_started(&client, &name, &user_1, &user_2);
+    assert_eq!(client.get_winner(&name), Some(user_1));
+}
+
+#[test]
+fn forge_count_tracks_each_class() {
+    let (_env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+
+    client.forge_blade(&user_1, &1);
+    client.melt_blade(&user_1, &1);
+    client.forge_blade(&user_1, &1);
+    client.forge_blade(&user_2, &2);
+    assert_eq!(client.get_forge_count(&1), 2);
+    assert_eq!(client.get_forge_count(&2), 1);
+    assert_eq!(client.get_forge_count(&3), 0);
+}
+
+#[test]
+fn defense_breaks_a_simultaneous_knockout() {
+    let (env, contract_id, user_1, user_2, _, _, attack, _, battle_name, client) =
+        setup_battle_sequence();
+
+    // Both hit harder than the other can take, but user_2 is better armoured
+    for (user, defense) in [(&user_1, 5), (&user_2, 20)] {
+        let mut stats = client.get_player_stats(user);
+        stats.health = 10;
+        stats.attack = 50;
+        stats.defense = defense;
+        env.as_contract(&contract_id, || {
+            BattleContract::set_player_stats(env.clone(), user.clone(), stats).unwrap()
+        });
+    }
+    client.attack_or_defend_choice(&user_1, &attack.unwrap(), &battle_name);
+    client.attack_or_defend_choice(&user_2, &attack.unwrap(), &battle_name);
+
+    let battle = client.get_battle(&battle_name);
+    assert_eq!(battle.battle_status, BattleStatus::Ended as u64);
+    assert!(!battle.is_draw);
+    assert_eq!(client.get_winner(&battle_name), Some(user_2.clone()));
+    let round = battle.rounds.last().unwrap();
+    assert_eq!(round.health.get(user_2.clone()), Some(1));
+    assert_eq!(round.health.get(user_1.clone()), Some(0));
+    assert_eq!(client.get_player_stats(&user_2).wins, 1);
+    assert_eq!(client.get_player_stats(&user_1).losses, 1);
+}
+
+#[test]
+fn sword_bonus_matches_forged_stats() {
+    let (env, _contract_id, _user_1, _user_2, client) = setup_test();
+    assert_eq!(client.get_sword_bonus(&2), (-3, 16, 2));
+    for class in 1..=3 {
+        let user = Address::random(&env);
+        client.add_player(&user);
+        let before = client.get_player_stats(&user);
+        client.forge_blade(&user, &class);
+        let after = client.get_player_stats(&user);
+        assert_eq!(
+            client.get_sword_bonus(&class),
+            (
+                after.health as i32 - before.health as i32,
+                after.attack as i32 - before.attack as i32,
+                after.defense as i32 - before.defense as i32,
+            )
+        );
+    }
+}
+
+#[test]
+fn moves_report_the_battle_result() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    pin_damage_variance(&env, &contract_id);
+    let set_health = |user: &Address, health: u32| {
+        let mut stats = client.get_player_stats(user);
+        stats.health = health;
+        stats.attack = 50;
+        env.as_contract(&contract_id, || {
+            BattleContract::set_player_stats(env.clone(), user.clone(), stats).unwrap()
+        });
+    };
+
+    // user_1 creates every battle, so they are player 1
+    let outcomes = [
+        ((100, 10), BattleResult::Player1Win),
+        ((10, 100), BattleResult::Player2Win),
+        ((10, 10), BattleResult::Draw),
+    ];
+    for (index, ((health_1, health_2), expected)) in outcomes.into_iter().enumerate() {
+        let name = Symbol::new(&env, ["Cannae", "Zama", "Trebia"][index]);
+        assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+        assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+        set_health(&user_1, health_1);
+        set_health(&user_2, health_2);
+
+        assert_eq!(
+            client.attack_or_defend_choice(&user_2, &1, &name),
+            BattleResult::Ongoing
+        );
+        assert_eq!(client.attack_or_defend_choice(&user_1, &1, &name), expected);
+        assert_eq!(client.get_battle(&name).result, expected);
+    }
+
+    let stats = client.get_player_stats(&user_1);
+    assert_eq!((stats.wins, stats.losses, stats.draws), (1, 1, 1));
+    assert_eq!(client.get_player_stats(&user_2).draws, 1);
+}
+
+#[test]
+fn version_reports_the_storage_layout() {
+    let (_env, _contract_id, _user_1, _user_2, client) = setup_test();
+    assert_eq!(client.version(), CONTRACT_VERSION);
+    assert_eq!(client.version(), 6);
+}
+
+// The player layout the contract first shipped with
+#[contracttype]
+struct LegacyPlayerStat {
+    player_address: Address,
+    sword_class: u32,
+    health: u32,
+    attack: u32,
+    defense: u32,
+    in_battle: bool,
+    has_sword: bool,
+}
+
+// The battle layout from before rounds were counted
+#[contracttype]
+struct LegacyBattle {
+    battle_status: u64,
+    name: Symbol,
+    players: Map<Address, u64>,
+    moves: Map<Address, u64>,
+    turns: u64,
+    winner: Address,
+    last_move_ts: u64,
+    is_draw: bool,
+    result: BattleResult,
+    rounds: Vec<Round>,
+    max_players: u32,
+    seed_commit: BytesN<32>,
+    revealed_seed: u64,
+}
+
+#[test]
+fn migrate_upgrades_old_records() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    let admin = Address::random(&env);
+    initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    client.add_player(&user_2);
+    client.forge_blade(&user_2, &2);
+    let mut current = client.get_player_stats(&user_2);
+    current.first_win_at = OptionalTimestamp::Some(5_000);
+    env.as_contract(&contract_id, || {
+        BattleContract::set_player_stats(env.clone(), user_2.clone(), current.clone()).unwrap()
+    });
+    let old_battle = Symbol::new(&env, "Zama");
+    let ended_battle = Symbol::new(&env, "Zela");
+
+    // Simulate a deployment from before schema versions were stored
+    env.as_contract(&contract_id, || {
+        let storage = env.storage().instance();
+        storage.remove(&DataKey::SchemaVersion);
+        storage.set(
+            &DataKey::Player(user_1.clone()),
+            &LegacyPlayerStat {
+                player_address: user_1.clone(),
+                sword_class: 1,
+                health: 108,
+                attack: 14,
+                defense: 13,
+                in_battle: false,
+                has_sword: true,
+            },
+        );
+        storage.set(
+            &DataKey::Players,
+            &vec![&env, user_2.clone(), user_1.clone()],
+        );
+        let round = Round {
+            moves: map![&env, (user_1.clone(), 2), (user_2.clone(), 2)],
+            health: map![&env, (user_1.clone(), 108), (user_2.clone(), 97)],
+            dealt: map![&env, (user_1.clone(), 0), (user_2.clone(), 0)],
+            taken: map![&env, (user_1.clone(), 0), (user_2.clone(), 0)],
+        };
+        storage.set(
+            &DataKey::Battle(old_battle.clone()),
+            &LegacyBattle {
+                battle_status: 1,
+                name: old_battle.clone(),
+                players: map![&env, (user_1.clone(), 1), (user_2.clone(), 2)],
+                moves: map![&env, (user_1.clone(), 1), (user_2.clone(), 0)],
+                turns: 1,
+                winner: contract_id.clone(),
+                last_move_ts: 500,
+                is_draw: false,
+                result: BattleResult::Ongoing,
+                rounds: vec![&env, round.clone(), round],
+                max_players: 2,
+                seed_commit: BytesN::from_array(&env, &[0; 32]),
+                revealed_seed: 0,
+            },
+        );
+        storage.set(
+            &DataKey::Battle(ended_battle.clone()),
+            &LegacyBattle {
+                battle_status: 2,
+                name: ended_battle.clone(),
+                players: map![&env, (user_1.clone(), 1), (user_2.clone(), 2)],
+                moves: map![&env, (user_1.clone(), 0), (user_2.clone(), 0)],
+                turns: 0,
+                winner: user_1.clone(),
+                last_move_ts: 400,
+                is_draw: false,
+                result: BattleResult::Player1Win,
+                rounds: Vec::new(&env),
+                max_players: 2,
+                seed_commit: BytesN::from_array(&env, &[0; 32]),
+                revealed_seed: 0,
+            },
+        );
+        storage.set(&DataKey::Battles, &vec![&env, old_battle.clone()]);
+        storage.set(&DataKey::BattleArchive, &vec![&env, ended_battle.clone()]);
+    });
+
+    assert!(client.try_migrate_players(&user_1).is_err());
+    client.migrate_players(&admin);
+    let stats = client.get_player_stats(&user_1);
+    assert_eq!(
+        stats,
+        PlayerStat {
+            player_address: user_1.clone(),
+            sword_class: 1,
+            health: 108,
+            attack: 14,
+            defense: 13,
+            base_health: 100,
+            base_attack: 10,
+            base_defense: 10,
+            in_battle: false,
+            has_sword: true,
+            rating: STARTING_RATING,
+            peak_rating: STARTING_RATING,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+            streak: 0,
+            experience: 0,
+            level: 1,
+            first_win_at: OptionalTimestamp::None,
+            durability: 20,
+            total_damage_taken: 0,
+            current_battle: Symbol::new(&env, ""),
+        }
+    );
+    // Players already in the current layout come through unchanged
+    assert_eq!(client.get_player_stats(&user_2), current);
+
+    // Battles gain the fields added since, and keep being fought
+    let battle = client.get_battle(&old_battle);
+    assert_eq!(battle.round_count, 2);
+    assert_eq!(battle.winner, OptionalAddress::None);
+    assert_eq!(battle.first_mover, OptionalAddress::None);
+    assert_eq!(battle.specials, map![&env]);
+    assert_eq!(battle.invited_at, OptionalLedger::None);
+    assert_eq!(battle.moves.get(user_1.clone()), Some(1));
+    // The contract's address only stood in for a winner until one was found
+    assert_eq!(
+        client.get_battle(&ended_battle).winner,
+        OptionalAddress::Some(user_1.clone())
+    );
+
+    // Migrating again changes nothing
+    client.migrate_players(&admin);
+    assert_eq!(client.get_player_stats(&user_1), stats);
+}
+
+#[test]
+fn bump_ttl_keeps_the_contract_alive() {
+    let (env, contract_id, user_1, _user_2, client) = setup_test();
+    let admin = Address::random(&env);
+    initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    client.add_player(&user_1);
+
+    client.bump_ttl(&admin, &(30 * 17280));
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(client.get_players(), vec![&env, user_1.clone()]);
+    assert_eq!(client.get_player_stats(&user_1).player_address, user_1);
+    assert!(client.try_bump_ttl(&user_1, &1).is_err());
+}
+
+#[test]
+fn potions_heal_up_to_full_health() {
+    let (env, contract_id, user_1, _user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.buy_potion(&user_1, &2);
+    assert_eq!(client.get_potions(&user_1), 2);
+
+    let mut stats = client.get_player_stats(&user_1);
+    stats.health = 60;
+    env.as_contract(&contract_id, || {
+        BattleContract::set_player_stats(env.clone(), user_1.clone(), stats).unwrap()
+    });
+
+    client.use_potion(&user_1);
+    assert_eq!(client.get_player_stats(&user_1).health, 60 + POTION_HEAL);
+    assert_eq!(client.get_potions(&user_1), 1);
+
+    // Healing stops at full health, and a healthy player can't waste one
+    client.use_potion(&user_1);
+    assert_eq!(client.get_player_stats(&user_1).health, MAX_HEALTH);
+    assert_eq!(client.get_potions(&user_1), 0);
+    client.buy_potion(&user_1, &1);
+    assert!(client.try_use_potion(&user_1).is_err());
+    assert_eq!(client.get_potions(&user_1), 1);
+}
+
+#[test]
+fn potions_cannot_be_used_in_battle() {
+    let (env, contract_id, _user_1, _user_2, client) = setup_test();
+    pin_damage_variance(&env, &contract_id);
+    let (name, user_1, user_2) = start_sword_battle(&env, &client, "Lepanto", 1, 1);
+    client.buy_potion(&user_1, &1);
+
+    let (taken, _) = attack_round(&client, &name, &user_1, &user_2);
+    assert!(taken > 0);
+    assert!(client.try_use_potion(&user_1).is_err());
+    assert_eq!(client.get_player_stats(&user_1).health, 108 - taken);
+    assert_eq!(client.get_potions(&user_1), 1);
+}
+
+#[test]
+fn move_history_keeps_the_latest_moves() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    let name = Symbol::new(&env, "Orleans");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+
+    client.attack_or_defend_choice(&user_1, &1, &name);
+    client.attack_or_defend_choice(&user_2, &2, &name);
+    assert_eq!(client.get_player_moves(&user_1), vec![&env, 1]);
+    assert_eq!(client.get_player_moves(&user_2), vec![&env, 2]);
+
+    // Nobody is hurt while both defend, so the battle lasts
+    for _ in 0..MAX_MOVE_HISTORY {
+        client.attack_or_defend_choice(&user_1, &2, &name);
+        client.attack_or_defend_choice(&user_2, &2, &name);
+    }
+    let moves = client.get_player_moves(&user_1);
+    assert_eq!(moves.len(), MAX_MOVE_HISTORY);
+    assert!(moves.iter().all(|choice| choice == 2));
+}
+
+#[test]
+fn wagered_battle_pays_the_pot_to_the_winner() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    let wager_token = env.register_stellar_asset_contract(Address::random(&env));
+    initialize_contract(
+        &env,
+        &contract_id,
+        &client,
+        &Address::random(&env),
+        &wager_token,
+    );
+    let balances = token::Client::new(&env, &wager_token);
+    for user in [&user_1, &user_2] {
+        token::StellarAssetClient::new(&env, &wager_token).mint(user, &100);
+        client.add_player(user);
+    }
+    client.forge_blade(&user_1, &2);
+    client.forge_blade(&user_2, &1);
+
+    // A cancelled wager goes back to the creator
+    let cancelled = Symbol::new(&env, "Crecy");
+    client.create_battle_with_wager(&cancelled, &user_1, &40);
+    assert_eq!(balances.balance(&user_1), 60);
+    client.cancel_battle(&user_1, &cancelled);
+    assert_eq!(balances.balance(&user_1), 100);
+    assert_eq!(client.get_wager(&cancelled), 0);
+
+    let name = Symbol::new(&env, "Agincourt");
+    client.create_battle_with_wager(&name, &user_1, &40);
+    assert_eq!(client.get_wager(&name), 40);
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+    assert_eq!(balances.balance(&contract_id), 80);
+    assert_eq!(balances.balance(&user_2), 60);
+
+    fight_to_the_end_started(&client, &name, &user_1, &user_2);
+    assert_eq!(client.get_winner(&name), Some(user_1.clone()));
+    assert_eq!(balances.balance(&user_1), 140);
+    assert_eq!(balances.balance(&user_2), 60);
+    assert_eq!(balances.balance(&contract_id), 0);
+    assert_eq!(client.get_wager(&name), 0);
+
+    // A wager handed to the bot stays the creator's if the bot wins
+    let bot_battle = Symbol::new(&env, "Poitiers");
+    client.create_battle_with_wager(&bot_battle, &user_2, &40);
+    assert_eq!(balances.balance(&user_2), 20);
+    client.challenge_bot(&user_2, &bot_battle);
+    client.forfeit_battle(&user_2, &bot_battle);
+    assert_eq!(client.get_winner(&bot_battle), Some(contract_id.clone()));
+    assert_eq!(balances.balance(&user_2), 60);
+    assert_eq!(balances.balance(&contract_id), 0);
+    assert_eq!(client.get_wager(&bot_battle), 0);
+}
+
+#[test]
+fn counts_track_players_and_battles() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    assert_eq!(client.player_count(), 0);
+    assert_eq!(client.battle_count(), 0);
+
+    client.add_players(&vec![&env, user_1.clone(), user_2.clone()]);
+    assert_eq!(client.player_count(), 2);
+
+    let name = Symbol::new(&env, "Marathon");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.battle_count(), 1);
+    assert_eq!(client.battle_count(), client.get_battles().len());
+
+    // Ended battles leave the battle list for the archive
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+    fight_to_the_end_started(&client, &name, &user_1, &user_2);
+    assert_eq!(client.battle_count(), 0);
+
+    client.remove_player(&user_2);
+    assert_eq!(client.player_count(), 1);
+}
+
+#[test]
+fn fusing_two_swords_forges_an_enhanced_one() {
+    let (env, contract_id, user_1, _user_2, client) = setup_test();
+    client.add_player(&user_1);
+    let base = client.get_player_stats(&user_1);
+    client.forge_blade(&user_1, &1);
+    env.as_contract(&contract_id, || {
+        sword_contract::mint(&env, user_1.clone(), 1, 1).unwrap()
+    });
+
+    client.fuse_blades(&user_1, &1);
+    let enhanced = 1 + ENHANCED_CLASS_OFFSET;
+    assert_eq!(client.get_sword_bonus(&enhanced), (16, 8, 6));
+    let player = client.get_player_stats(&user_1);
+    assert_eq!(player.sword_class, enhanced);
+    assert!(player.has_sword);
+    assert_eq!(player.health, base.health + 16);
+    assert_eq!(player.attack, base.attack + 8);
+    assert_eq!(player.defense, base.defense + 6);
+
+    let balances = env.as_contract(&contract_id, || {
+        (
+            SwordContract::balance_of(env.clone(), user_1.clone(), 1),
+            SwordContract::balance_of(env.clone(), user_1.clone(), enhanced),
+        )
+    });
+    assert_eq!(balances, (0, 1));
+    let inventory = client.inventory(&user_1);
+    assert_eq!(inventory.len(), 1);
+    assert_eq!(inventory.get(0).unwrap().class, enhanced);
+}
+
+#[test]
+fn enhanced_swords_keep_their_base_class_traits() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    for class in 1..=3 {
+        assert_eq!(
+            BattleContract::crit_chance(class + ENHANCED_CLASS_OFFSET),
+            BattleContract::crit_chance(class)
+        );
+    }
+
+    client.add_players(&vec![&env, user_1.clone(), user_2.clone()]);
+    client.forge_blade(&user_1, &2);
+    env.as_contract(&contract_id, || {
+        sword_contract::mint(&env, user_1.clone(), 2, 1).unwrap()
+    });
+    client.fuse_blades(&user_1, &2);
+    assert_eq!(client.get_player_stats(&user_1).health, 94);
+
+    let name = Symbol::new(&env, "Ramillies");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+    env.as_contract(&contract_id, || {
+        let mut stats = BattleContract::get_player_stats(env.clone(), user_1.clone());
+        stats.health = 90;
+        BattleContract::set_player_stats(env.clone(), user_1.clone(), stats).unwrap();
+    });
+
+    // The enhanced Sabre heals no further than its own maximum health
+    for _ in 0..10 {
+        client.attack_or_defend_choice(&user_1, &2, &name);
+        client.attack_or_defend_choice(&user_2, &2, &name);
+    }
+    assert_eq!(client.get_player_stats(&user_1).health, 94);
+}
+
+#[test]
+fn reset_player_restores_base_stats() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    let admin = Address::random(&env);
+    initialize_contract(&env, &contract_id, &client, &admin, &Address::random(&env));
+    client.add_players(&vec![&env, user_1.clone(), user_2.clone()]);
+    client.forge_blade(&user_1, &2);
+    client.forge_blade(&user_2, &2);
+    let forged = client.get_player_stats(&user_1);
+
+    let name = Symbol::new(&env, "Tours");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+    let mut stat = client.get_player_stats(&user_1);
+    stat.health = 7;
+    stat.attack = 1;
+    stat.defense = 0;
+    env.as_contract(&contract_id, || {
+        BattleContract::set_player_stats(env.clone(), user_1.clone(), stat).unwrap()
+    });
+
+    client.reset_player(&admin, &user_1, &true);
+    let player = client.get_player_stats(&user_1);
+    assert!(!player.in_battle);
+    assert_eq!(player.current_battle, Symbol::new(&env, ""));
+    assert_eq!(
+        (player.health, player.attack, player.defense),
+        (forged.health, forged.attack, forged.defense)
+    );
+    assert!(player.has_sword);
+
+    // Clearing the sword leaves its NFT with the player
+    client.reset_player(&admin, &user_2, &false);
+    let player = client.get_player_stats(&user_2);
+    assert!(!player.has_sword);
+    assert_eq!(
+        (player.health, player.attack, player.defense),
+        (player.base_health, player.base_attack, player.base_defense)
+    );
+    assert_eq!(client.inventory(&user_2).len(), 1);
+}
+
+#[test]
+fn round_cap_ends_a_stalled_battle_on_health() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_players(&vec![&env, user_1.clone(), user_2.clone()]);
+    // The Sabre's health penalty leaves its wielder behind on health
+    client.forge_blade(&user_1, &2);
+    let name = Symbol::new(&env, "Verdun");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+
+    for _ in 0..MAX_ROUNDS - 1 {
+        client.attack_or_defend_choice(&user_1, &2, &name);
+        client.attack_or_defend_choice(&user_2, &2, &name);
+    }
+    let battle = client.get_battle(&name);
+    assert_eq!(battle.round_count, MAX_ROUNDS - 1);
+    assert_eq!(battle.battle_status, 1);
+
+    client.attack_or_defend_choice(&user_1, &2, &name);
+    assert_eq!(
+        client.attack_or_defend_choice(&user_2, &2, &name),
+        BattleResult::Player2Win
+    );
+    let battle = client.get_battle(&name);
+    assert_eq!(battle.round_count, MAX_ROUNDS);
+    assert_eq!(client.get_winner(&name), Some(user_2));
+}
+
+#[test]
+fn round_cap_ends_a_stalled_free_for_all_on_health() {
+    let (env, _contract_id, _user_1, _user_2, client) = setup_test();
+    let users = [
+        Address::random(&env),
+        Address::random(&env),
+        Address::random(&env),
+    ];
+    client.add_players(&vec![
+        &env,
+        users[0].clone(),
+        users[1].clone(),
+        users[2].clone(),
+    ]);
+    // Nobody is hurt while everyone defends, so health is decided by swords
+    let stall = |name: &Symbol| {
+        assert_eq!(client.create_battle(name, &users[0], &3), (Ok(()), Ok(())));
+        assert_eq!(client.join_battle(name, &users[1]), (Ok(()), Ok(())));
+        assert_eq!(client.join_battle(name, &users[2]), (Ok(()), Ok(())));
+        for _ in 0..MAX_ROUNDS {
+            for user in users.iter() {
+                client.attack_or_defend_choice(user, &2, name);
+            }
+        }
+        let battle = client.get_battle(name);
+        assert_eq!(battle.round_count, MAX_ROUNDS);
+        assert_eq!(battle.battle_status, 2);
+        battle
+    };
+
+    // The Sabre leaves its wielder behind, and the other two tie for the lead
+    client.forge_blade(&users[0], &2);
+    let battle = stall(&Symbol::new(&env, "Verdun"));
+    assert!(battle.is_draw);
+    assert_eq!(battle.result, BattleResult::Draw);
+    assert_eq!(client.get_player_stats(&users[0]).losses, 1);
+    assert_eq!(client.get_player_stats(&users[1]).draws, 1);
+    assert_eq!(client.get_player_stats(&users[2]).draws, 1);
+
+    // A Longsword puts its wielder ahead of everyone else
+    client.forge_blade(&users[1], &1);
+    let name = Symbol::new(&env, "Somme");
+    let battle = stall(&name);
+    assert!(!battle.is_draw);
+    assert_eq!(client.get_winner(&name), Some(users[1].clone()));
+    assert_eq!(client.get_player_stats(&users[0]).losses, 2);
+    assert_eq!(client.get_player_stats(&users[1]).wins, 1);
+    assert_eq!(client.get_player_stats(&users[2]).losses, 1);
+    for user in users.iter() {
+        assert!(!client.get_player_stats(user).in_battle);
+    }
+}
+
+#[test]
+fn battle_players_are_listed_in_slot_order() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_players(&vec![&env, user_1.clone(), user_2.clone()]);
+    let name = Symbol::new(&env, "Salamis");
+    assert_eq!(client.create_battle(&name, &user_2, &2), (Ok(()), Ok(())));
+    assert_eq!(client.get_battle_players(&name), vec![&env, user_2.clone()]);
+
+    assert_eq!(client.join_battle(&name, &user_1), (Ok(()), Ok(())));
+    assert_eq!(
+        client.get_battle_players(&name),
+        vec![&env, user_2.clone(), user_1.clone()]
+    );
+}
+
+#[test]
+fn light_heavy_and_defend_beat_each_other_in_turn() {
+    let (env, contract_id, user_1, user_2, client) = setup_test();
+    pin_damage_variance(&env, &contract_id);
+    client.add_players(&vec![&env, user_1.clone(), user_2.clone()]);
+    let name = Symbol::new(&env, "Gaugamela");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+
+    // Both players have 10 attack and 10 defense. A light attack deals
+    // 10 - 10 / 2 = 5 and a heavy one 15 - 10 / 2 = 10, unless blocked.
+    let light = 1;
+    let defend = 2;
+    let heavy = 3;
+    let combinations = [
+        (light, light, 5, 5),
+        (light, defend, 0, 0),
+        (light, heavy, 0, 5),
+        (defend, light, 0, 0),
+        (defend, defend, 0, 0),
+        (defend, heavy, 10, 0),
+        (heavy, light, 5, 0),
+        (heavy, defend, 0, 10),
+        (heavy, heavy, 10, 10),
+    ];
+    for (move_1, move_2, taken_1, taken_2) in combinations {
+        client.attack_or_defend_choice(&user_1, &move_1, &name);
+        client.attack_or_defend_choice(&user_2, &move_2, &name);
+        let battle = client.get_battle(&name);
+        let round = battle.rounds.last().unwrap();
+        assert_eq!(
+            (
+                round.taken.get_unchecked(user_1.clone()),
+                round.taken.get_unchecked(user_2.clone())
+            ),
+            (taken_1, taken_2),
+            "{} against {}",
+            move_1,
+            move_2
+        );
+    }
+}
+
+#[test]
+fn battle_log_records_ended_battles_in_order() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_players(&vec![&env, user_1.clone(), user_2.clone()]);
+    // A Sabre always outlasts a Longsword when both keep attacking
+    client.forge_blade(&user_1, &2);
+    client.forge_blade(&user_2, &1);
+
+    let first = Symbol::new(&env, "Thermopylae");
+    let second = Symbol::new(&env, "Plataea");
+    env.ledger().with_mut(|li| li.timestamp = 1_000);
+    fight_to_the_end(&client, &first, &user_1, &user_2);
+    env.ledger().with_mut(|li| li.timestamp = 2_000);
+    fight_to_the_end(&client, &second, &user_2, &user_1);
+
+    let log = client.get_battle_log(&0, &10);
+    assert_eq!(
+        log,
+        vec![
+            &env,
+            BattleLogEntry {
+                name: first,
+                player_a: user_1.clone(),
+                player_b: user_2.clone(),
+                winner: OptionalAddress::Some(user_1.clone()),
+                is_draw: false,
+                ended_at: 1_000,
+            },
+            BattleLogEntry {
+                name: second.clone(),
+                player_a: user_2.clone(),
+                player_b: user_1.clone(),
+                winner: OptionalAddress::Some(user_1.clone()),
+                is_draw: false,
+                ended_at: 2_000,
+            },
+        ]
+    );
+    assert_eq!(client.get_battle_log(&1, &10).get(0).unwrap().name, second);
+    assert_eq!(client.get_battle_log(&2, &10).len(), 0);
+}
+
+#[test]
+fn auto_battles_hold_their_creator() {
+    let (env, _contract_id, user_1, _user_2, client) = setup_test();
+    client.add_player(&user_1);
+    let name = Symbol::new(&env, "Blenheim");
+    client.create_auto_battle(&name, &user_1);
+    assert_eq!(client.get_player_battle(&user_1), Some(name.clone()));
+
+    // One battle at a time, bot or not
+    assert!(client
+        .try_create_auto_battle(&Symbol::new(&env, "Oudenarde"), &user_1)
+        .is_err());
+    client.forfeit_battle(&user_1, &name);
+    assert!(!client.get_player_stats(&user_1).in_battle);
+}
+
+#[test]
+fn unregistered_players_cannot_battle() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    let name = Symbol::new(&env, "Cannae");
+    assert_eq!(
+        client.try_create_battle(&name, &user_2, &2),
+        Err(Ok(BattleError::NotRegistered.into()))
+    );
+    assert_eq!(
+        client.try_challenge_bot(&user_2, &name),
+        Err(Ok(BattleError::NotRegistered.into()))
+    );
+    assert_eq!(
+        client.try_create_auto_battle(&name, &user_2),
+        Err(Ok(BattleError::NotRegistered.into()))
+    );
+    assert_eq!(
+        client.try_join_matchmaking(&user_2),
+        Err(Ok(BattleError::NotRegistered.into()))
+    );
+
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(
+        client.try_join_battle(&name, &user_2),
+        Err(Ok(BattleError::NotRegistered.into()))
+    );
+    assert_eq!(client.get_battle(&name).battle_status, 0);
+}
+
+#[test]
+fn first_mover_is_known_until_the_round_resolves() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_players(&vec![&env, user_1.clone(), user_2.clone()]);
+    let name = Symbol::new(&env, "Lepanto");
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+    assert_eq!(client.get_battle(&name).first_mover, OptionalAddress::None);
+
+    client.attack_or_defend_choice(&user_1, &2, &name);
+    assert_eq!(
+        client.get_battle(&name).first_mover,
+        OptionalAddress::Some(user_1.clone())
+    );
+
+    client.attack_or_defend_choice(&user_2, &2, &name);
+    assert_eq!(client.get_battle(&name).first_mover, OptionalAddress::None);
+
+    client.attack_or_defend_choice(&user_2, &2, &name);
+    assert_eq!(
+        client.get_battle(&name).first_mover,
+        OptionalAddress::Some(user_2.clone())
+    );
+}
+
+// Starts a battle between two fresh players wielding the given swords
+fn start_sword_battle(
+    env: &Env,
+    client: &BattleContractClient<'static>,
+    name: &str,
+    class_1: u32,
+    class_2: u32,
+) -> (Symbol, Address, Address) {
+    let user_1 = Address::random(env);
+    let user_2 = Address::random(env);
+    client.add_players(&vec![env, user_1.clone(), user_2.clone()]);
+    client.forge_blade(&user_1, &class_1);
+    client.forge_blade(&user_2, &class_2);
+    let name = Symbol::new(env, name);
+    assert_eq!(client.create_battle(&name, &user_1, &2), (Ok(()), Ok(())));
+    assert_eq!(client.join_battle(&name, &user_2), (Ok(()), Ok(())));
+    (name, user_1, user_2)
+}
+
+// The damage each player took in a round where both attacked
+fn attack_round(
+    client: &BattleContractClient<'static>,
+    name: &Symbol,
+    user_1: &Address,
+    user_2: &Address,
+) -> (u32, u32) {
+    client.attack_or_defend_choice(user_1, &1, name);
+    client.attack_or_defend_choice(user_2, &1, name);
+    let round = client.get_battle(name).rounds.last().unwrap();
+    (
+        round.taken.get_unchecked(user_1.clone()),
+        round.taken.get_unchecked(user_2.clone()),
+    )
+}
+
+#[test]
+fn players_leave_a_battle_at_full_sword_health() {
+    let (env, contract_id, _user_1, _user_2, client) = setup_test();
+    pin_damage_variance(&env, &contract_id);
+    let (name, user_1, user_2) = start_sword_battle(&env, &client, "Jena", 1, 2);
+    assert_eq!(client.get_player_stats(&user_1).health, 108);
+    assert_eq!(client.get_player_stats(&user_2).health, 97);
+
+    attack_round(&client, &name, &user_1, &user_2);
+    client.forfeit_battle(&user_1, &name);
+    let stats_1 = client.get_player_stats(&user_1);
+    let stats_2 = client.get_player_stats(&user_2);
+    assert!(stats_1.has_sword && stats_2.has_sword);
+    assert_eq!(stats_1.health, 108);
+    assert_eq!(stats_2.health, 97);
+}
+
+#[test]
+fn sword_specials_change_one_round() {
+    let (env, contract_id, _user_1, _user_2, client) = setup_test();
+    pin_damage_variance(&env, &contract_id);
+
+    // Flurry doubles the Sabre's hit
+    let (name, sabre, longsword) = start_sword_battle(&env, &client, "Zama", 2, 1);
+    let normal = attack_round(&client, &name, &sabre, &longsword);
+    client.use_special(&sabre, &name);
+    assert_eq!(
+        attack_round(&client, &name, &sabre, &longsword),
+        (normal.0, normal.1 * 2)
+    );
+    assert_eq!(attack_round(&client, &name, &sabre, &longsword), normal);
+
+    // Cleave ignores the Longsword's defense
+    let (name, claymore, longsword) = start_sword_battle(&env, &client, "Pydna", 3, 1);
+    let normal = attack_round(&client, &name, &claymore, &longsword);
+    client.use_special(&claymore, &name);
+    let claymore_attack = client.get_player_stats(&claymore).attack;
+    assert_eq!(
+        attack_round(&client, &name, &claymore, &longsword),
+        (normal.0, claymore_attack)
+    );
+
+    // Parry negates the hit the Longsword takes
+    let (name, sabre, longsword) = start_sword_battle(&env, &client, "Magnesia", 2, 1);
+    let normal = attack_round(&client, &name, &sabre, &longsword);
+    client.use_special(&longsword, &name);
+    assert_eq!(
+        attack_round(&client, &name, &sabre, &longsword),
+        (normal.0, 0)
+    );
+    assert_eq!(
+        client.get_battle(&name).specials,
+        map![&env, (longsword.clone(), false)]
+    );
+    assert_eq!(
+        client.try_use_special(&longsword, &name),
+        Err(Ok(BattleError::SpecialUsed.into()))
+    );
+}
+
+#[test]
+fn equipped_sword_uri_follows_the_wielded_class() {
+    let (env, _contract_id, user_1, _user_2, client) = setup_test();
+    client.add_player(&user_1);
+    assert_eq!(
+        client.get_equipped_sword_uri(&user_1),
+        String::from_slice(&env, "")
+    );
+
+    client.forge_blade(&user_1, &2);
+    assert_eq!(
+        client.get_equipped_sword_uri(&user_1),
+        String::from_slice(&env, "https://example/token1")
+    );
+
+    client.melt_blade(&user_1, &2);
+    assert_eq!(
+        client.get_equipped_sword_uri(&user_1),
+        String::from_slice(&env, "")
+    );
+}
+
+#[test]
+fn sword_contract_initializes_once() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let sword_id = env.register_contract(None, SwordContract);
+    let sword = sword_contract::SwordContractClient::new(&env, &sword_id);
+    let admin = Address::random(&env);
+
+    sword.initialize(&admin, &Address::random(&env));
+    assert_eq!(sword.admin(), admin);
+
+    // A second initialize can't take over the collection
+    assert!(sword
+        .try_initialize(&Address::random(&env), &Address::random(&env))
+        .is_err());
+    assert_eq!(sword.admin(), admin);
+}
+
+#[test]
+fn only_the_minter_or_admin_can_mint() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let (sword, admin, minter) = setup_sword_contract(&env);
+    let user_1 = Address::random(&env);
+
+    sword.mint_nft(&minter, &user_1, &1, &1);
+    sword.mint_nft(&admin, &user_1, &2, &1);
+    assert_eq!(sword.balance_of(&user_1, &1), 1);
+    assert_eq!(sword.balance_of(&user_1, &2), 1);
+
+    // Holders can't mint to themselves or burn on their own
+    assert!(sword.try_mint_nft(&user_1, &user_1, &3, &1).is_err());
+    assert!(sword.try_melt_blade(&user_1, &user_1, &1).is_err());
+    assert_eq!(sword.balance_of(&user_1, &3), 0);
+    assert_eq!(sword.balance_of(&user_1, &1), 1);
+}
+
+#[test]
+fn balances_of_lists_every_class() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let (sword, _admin, minter) = setup_sword_contract(&env);
+    let user_1 = Address::random(&env);
+
+    sword.mint_nft(&minter, &user_1, &1, &2);
+    sword.mint_nft(&minter, &user_1, &3, &1);
+    assert_eq!(
+        sword.balances_of(&user_1),
+        map![&env, (1, 2), (2, 0), (3, 1), (4, 0), (5, 0), (6, 0)]
+    );
+    assert_eq!(
+        sword.balances_of(&Address::random(&env)),
+        map![&env, (1, 0), (2, 0), (3, 0), (4, 0), (5, 0), (6, 0)]
+    );
+}
+
+#[test]
+fn stat_increases_stop_at_their_caps() {
+    let (env, contract_id, user_1, _user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.forge_blade(&user_1, &2);
+    let mut stat = client.get_player_stats(&user_1);
+    let sword_attack = stat.attack - stat.base_attack;
+    let sword_defense = stat.defense - stat.base_defense;
+
+    assert_eq!(BattleContract::increase_attack(&mut stat, 1), Ok(()));
+    assert_eq!(BattleContract::increase_defense(&mut stat, 1), Ok(()));
+    // Increases past the cap saturate at it and report the cap
+    let capped = Err(BattleError::StatCapReached.into());
+    assert_eq!(BattleContract::increase_attack(&mut stat, u32::MAX), capped);
+    assert_eq!(
+        BattleContract::increase_defense(&mut stat, u32::MAX),
+        capped
+    );
+    assert_eq!(BattleContract::increase_attack(&mut stat, 1), capped);
+    assert_eq!(BattleContract::increase_defense(&mut stat, 1), capped);
+    assert_eq!(stat.base_attack, MAX_ATTACK);
+    assert_eq!(stat.base_defense, MAX_DEFENSE);
+    // The sword's bonus still applies on top of the capped stats
+    assert_eq!(stat.attack, MAX_ATTACK + sword_attack);
+    assert_eq!(stat.defense, MAX_DEFENSE + sword_defense);
+
+    // Levels keep counting at the caps, and the capped stats are reported
+    let level = stat.level;
+    env.as_contract(&contract_id, || {
+        BattleContract::award_experience(&env, &mut stat, EXPERIENCE_PER_LEVEL)
+    });
+    assert_eq!(stat.level, level + 1);
+    assert_eq!(stat.base_attack, MAX_ATTACK);
+    assert_eq!(
+        last_event(&env),
+        vec![
+            &env,
+            (
+                contract_id.clone(),
+                (symbol_short!("player"), symbol_short!("capped")).into_val(&env),
+                (user_1.clone(), symbol_short!("defense")).into_val(&env),
+            ),
+        ]
+    );
+
+    // Healing saturates at the Sabre's maximum health
+    stat.health = 1;
+    BattleContract::apply_heal(&mut stat, u32::MAX);
+    assert_eq!(stat.health, MAX_HEALTH - 3);
+
+    // Damage saturates at zero health
+    let health = env.as_contract(&contract_id, || {
+        BattleContract::decrease_health(env.clone(), user_1.clone(), u32::MAX)
+    });
+    assert_eq!(health, 0);
+}
+
+#[test]
+fn declining_an_invite_frees_both_players() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    let user_3 = Address::random(&env);
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    client.add_player(&user_3);
+    let name = Symbol::new(&env, "Agincourt");
+    client.invite_player(&name, &user_1, &user_2);
+    assert_eq!(client.get_invite(&name), Some(user_2.clone()));
+    assert!(client.get_player_stats(&user_1).in_battle);
+
+    // Only the invited player can join or decline
+    assert!(client.try_join_battle(&name, &user_3).is_err());
+    assert!(client.try_decline_invite(&user_3, &name).is_err());
+
+    client.decline_invite(&user_2, &name);
+    assert!(!client.get_player_stats(&user_1).in_battle);
+    assert!(!client.get_player_stats(&user_2).in_battle);
+    assert!(!client.get_battles().contains(name.clone()));
+    assert_eq!(client.get_invite(&name), None);
+}
+
+#[test]
+fn challenger_reclaims_an_expired_invite() {
+    let (env, _contract_id, user_1, user_2, client) = setup_test();
+    client.add_player(&user_1);
+    client.add_player(&user_2);
+    let name = Symbol::new(&env, "Crecy");
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    client.invite_player(&name, &user_1, &user_2);
+
+    // The invite can't be cancelled or reclaimed while it's still open
+    assert!(client.try_cancel_battle(&user_1, &name).is_err());
+    env.ledger()
+        .with_mut(|li| li.sequence_number = 99 + INVITE_EXPIRY_LEDGERS);
+    assert!(client.try_reclaim_invite(&user_1, &name).is_err());
+
+    env.ledger()
+        .with_mut(|li| li.sequence_number = 100 + INVITE_EXPIRY_LEDGERS);
+    client.reclaim_invite(&user_1, &name);
+    assert!(!client.get_player_stats(&user_1).in_battle);
+    assert!(!client.get_battles().contains(name.clone()));
+    assert_eq!(client.get_invite(&name), None);
+}